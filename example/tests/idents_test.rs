@@ -0,0 +1,53 @@
+use prost_wkt_types::*;
+
+// Messages with names prost escapes or converts in unusual ways, see `proto/idents.proto`. The
+// generated implementations have to refer to the types prost generated to compile at all.
+include!(concat!(env!("OUT_DIR"), "/my.idents.rs"));
+
+#[test]
+fn test_pack_unpack_idents() {
+    let msg = Type {
+        r#match: Some(r#type::Match::FooBar2(FooBar2 {
+            value: "nested".to_string(),
+        })),
+        r#where: r#type::Kind::Async as i32,
+        r#await: Some(r#type::Async {
+            value: "async".to_string(),
+        }),
+    };
+    let any = Any::try_pack(msg.clone()).unwrap();
+    assert_eq!(any.type_url, "type.googleapis.com/my.idents.type");
+    let unpacked: Type = any.unpack_as(Type::default()).unwrap();
+    assert_eq!(unpacked, msg);
+
+    let any = Any::try_pack(Self_ { value: 1 }).unwrap();
+    assert_eq!(any.type_url, "type.googleapis.com/my.idents.Self");
+    let unpacked = any.try_unpack().unwrap();
+    assert_eq!(unpacked.message_name(), "Self");
+    assert_eq!(unpacked.downcast_ref::<Self_>(), Some(&Self_ { value: 1 }));
+
+    let any = Any::try_pack(r#type::Async::default()).unwrap();
+    assert_eq!(any.type_url, "type.googleapis.com/my.idents.type.async");
+}
+
+#[test]
+fn test_json_idents() {
+    let msg = Type {
+        r#match: Some(r#type::Match::Self_("value".to_string())),
+        r#where: r#type::Kind::Async as i32,
+        r#await: None,
+    };
+    let json = serde_json::to_value(&msg).unwrap();
+    assert_eq!(json, serde_json::json!({"self": "value", "where": "KIND_ASYNC"}));
+    assert_eq!(serde_json::from_value::<Type>(json).unwrap(), msg);
+
+    let mut request = XmlHttpRequest {
+        self_: Some(Self_ { value: 2 }),
+        nested: Some(r#type::Async {
+            value: "nested".to_string(),
+        }),
+    };
+    FieldMask::from_paths(["nested.value"]).trim(&mut request);
+    assert_eq!(request.self_, None);
+    assert_eq!(request.nested.unwrap().value, "nested");
+}
@@ -0,0 +1,26 @@
+use prost_wkt_types::*;
+
+include!(concat!(env!("OUT_DIR"), "/my.messages.rs"));
+
+#[test]
+fn test_json_wrappers() {
+    let counter = Counter {
+        count: Some(Int64Value::from(i64::MAX)),
+        checksum: Some(BytesValue::from(vec![0xfb, 0xff])),
+    };
+    let json = serde_json::to_value(&counter).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({"count": "9223372036854775807", "checksum": "+/8="})
+    );
+    assert_eq!(serde_json::from_value::<Counter>(json).unwrap(), counter);
+
+    // Wrappers are written even when they hold the default value, as they are set.
+    let counter = Counter {
+        count: Some(Int64Value::from(0)),
+        checksum: None,
+    };
+    let json = serde_json::to_value(&counter).unwrap();
+    assert_eq!(json, serde_json::json!({"count": "0"}));
+    assert_eq!(serde_json::from_value::<Counter>(json).unwrap(), counter);
+}
@@ -0,0 +1,150 @@
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+/// Trait to apply `FieldMask` paths to `prost` messages. Implementations are generated by
+/// `prost-wkt-build` for every message; `prost_wkt_types::FieldMask` provides the user facing
+/// `merge` and `trim` methods on top of it.
+pub trait FieldMaskable {
+    /// Returns `true` if `path` names a field of this message. Paths descending into singular
+    /// message fields, e.g. `foo.bar`, are checked against the nested message as well.
+    fn is_valid_path(path: &str) -> bool;
+
+    /// Copies the field named by `path` from `src` into `self`. Unknown paths are ignored.
+    fn merge_path(&mut self, src: &Self, path: &str, options: &MergeOptions);
+
+    /// Clears every field that is not covered by one of `paths`. The paths are expected to be
+    /// normalized, i.e. a path is never accompanied by one of its sub paths.
+    fn trim_paths(&mut self, paths: &[&str]);
+}
+
+/// Options controlling how `FieldMaskable::merge_path` copies fields.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MergeOptions {
+    /// When `true` a message field is replaced by the source message, clearing it if the source
+    /// field is unset. By default the source message is merged into the destination message.
+    pub replace_message_fields: bool,
+    /// When `true` a repeated or map field is replaced by the source field. By default the source
+    /// elements are appended to the destination.
+    pub replace_repeated_fields: bool,
+}
+
+impl<T: FieldMaskable> FieldMaskable for Box<T> {
+    fn is_valid_path(path: &str) -> bool {
+        T::is_valid_path(path)
+    }
+
+    fn merge_path(&mut self, src: &Self, path: &str, options: &MergeOptions) {
+        self.as_mut().merge_path(src, path, options)
+    }
+
+    fn trim_paths(&mut self, paths: &[&str]) {
+        self.as_mut().trim_paths(paths)
+    }
+}
+
+/// The parts of a list of field mask paths that concern a single field.
+#[doc(hidden)]
+pub enum FieldPaths<'a> {
+    /// The whole field is covered.
+    All,
+    /// The field is not covered at all.
+    None,
+    /// Only the given sub paths of the field are covered.
+    Nested(Vec<&'a str>),
+}
+
+/// Selects the paths concerning `field` from a normalized list of paths.
+#[doc(hidden)]
+pub fn field_paths<'a>(paths: &[&'a str], field: &str) -> FieldPaths<'a> {
+    let mut nested = Vec::new();
+    for path in paths {
+        if *path == field {
+            return FieldPaths::All;
+        }
+        if let Some(rest) = path
+            .strip_prefix(field)
+            .and_then(|rest| rest.strip_prefix('.'))
+        {
+            nested.push(rest);
+        }
+    }
+    if nested.is_empty() {
+        FieldPaths::None
+    } else {
+        FieldPaths::Nested(nested)
+    }
+}
+
+/// Checks `path` against the message type of a singular message field. The field accessor is only
+/// used to infer the nested message type, so generated code does not need to name it.
+#[doc(hidden)]
+pub fn is_valid_nested_path<M, F: FieldMaskable>(_field: fn(&M) -> Option<&F>, path: &str) -> bool {
+    F::is_valid_path(path)
+}
+
+/// Merges a whole singular message field.
+#[doc(hidden)]
+pub fn merge_message<M: prost::Message + Clone>(dst: &mut Option<M>, src: &Option<M>, options: &MergeOptions) {
+    match (dst.as_mut(), src) {
+        (_, None) if !options.replace_message_fields => {}
+        (Some(target), Some(source)) if !options.replace_message_fields => {
+            // Both values are valid messages, so merging the encoded source cannot fail.
+            let _ = target.merge(source.encode_to_vec().as_slice());
+        }
+        _ => *dst = src.clone(),
+    }
+}
+
+/// Merges a sub path of a singular message field.
+#[doc(hidden)]
+pub fn merge_nested<M: FieldMaskable + Default>(dst: &mut Option<M>, src: &Option<M>, path: &str, options: &MergeOptions) {
+    if dst.is_none() && src.is_none() {
+        return;
+    }
+    let default = M::default();
+    let source = src.as_ref().unwrap_or(&default);
+    dst.get_or_insert_with(M::default).merge_path(source, path, options);
+}
+
+/// Trims a singular message field to the given sub paths.
+#[doc(hidden)]
+pub fn trim_nested<M: FieldMaskable>(field: &mut Option<M>, paths: &[&str]) {
+    if let Some(value) = field.as_mut() {
+        value.trim_paths(paths);
+    }
+}
+
+/// Repeated and map fields that can be appended to when merging.
+#[doc(hidden)]
+pub trait RepeatedField: Clone {
+    fn append_from(&mut self, other: &Self);
+}
+
+impl<T: Clone> RepeatedField for Vec<T> {
+    fn append_from(&mut self, other: &Self) {
+        self.extend_from_slice(other);
+    }
+}
+
+impl<K: Clone + Eq + Hash, V: Clone> RepeatedField for HashMap<K, V> {
+    fn append_from(&mut self, other: &Self) {
+        self.extend(other.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+}
+
+impl<K: Clone + Ord, V: Clone> RepeatedField for BTreeMap<K, V> {
+    fn append_from(&mut self, other: &Self) {
+        self.extend(other.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+}
+
+/// Merges a repeated or map field.
+#[doc(hidden)]
+pub fn merge_repeated<R: RepeatedField>(dst: &mut R, src: &R, options: &MergeOptions) {
+    if options.replace_repeated_fields {
+        *dst = src.clone();
+    } else {
+        dst.append_from(src);
+    }
+}
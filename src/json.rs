@@ -0,0 +1,644 @@
+//! Serde helpers for fields whose proto3 JSON representation differs from serde's default, as
+//! referenced by the field attributes `prost-wkt-build` generates: 64 bit integers are written as
+//! strings, bytes as base64 and non-finite floating point numbers as `"NaN"`, `"Infinity"` and
+//! `"-Infinity"`. On input, numbers and strings are both accepted for numbers, standard and URL
+//! safe base64 for bytes, and `null` for the default value.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use base64::Engine;
+use serde::de::{self, Deserialize, Deserializer, MapAccess, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+/// Returns `true` if `value` is the default value of its type, for `skip_serializing_if`.
+pub fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+/// A field type with a proto3 JSON representation that differs from serde's default. Implemented
+/// for the Rust types prost generates for integer, floating point and bytes fields, and used by the
+/// wrapper types of `prost-wkt-types` as well.
+pub trait JsonScalar: Sized {
+    fn serialize_json<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>;
+    fn deserialize_json<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>;
+}
+
+/// Accepts a JSON number or a string holding an integer, checking that it fits in `T`.
+struct IntVisitor<T>(PhantomData<T>);
+
+impl<'de, T> Visitor<'de> for IntVisitor<T>
+where
+    T: TryFrom<i64> + TryFrom<u64> + FromStr + Default,
+{
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an integer as number or string")
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+        T::try_from(value).map_err(|_| E::invalid_value(de::Unexpected::Signed(value), &self))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+        T::try_from(value).map_err(|_| E::invalid_value(de::Unexpected::Unsigned(value), &self))
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Self::Value, E> {
+        let invalid = || E::invalid_value(de::Unexpected::Float(value), &self);
+        if value.fract() != 0.0 || !value.is_finite() {
+            return Err(invalid());
+        }
+        // Float to integer casts saturate, so the bounds are checked before casting. `i64::MIN` is
+        // exactly -2^63 as a float, while `i64::MAX` and `u64::MAX` round up to 2^63 and 2^64.
+        let result = if (i64::MIN as f64..0.0).contains(&value) {
+            T::try_from(value as i64).ok()
+        } else if (0.0..u64::MAX as f64).contains(&value) {
+            T::try_from(value as u64).ok()
+        } else {
+            None
+        };
+        result.ok_or_else(invalid)
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        value
+            .parse()
+            .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(T::default())
+    }
+}
+
+macro_rules! json_int {
+    ($type:ty, $serialize:expr) => {
+        impl JsonScalar for $type {
+            fn serialize_json<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                $serialize(self, serializer)
+            }
+
+            fn deserialize_json<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                deserializer.deserialize_any(IntVisitor::<$type>(PhantomData))
+            }
+        }
+    };
+}
+
+fn serialize_display<T: fmt::Display, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+// 32 bit integers are written as numbers, 64 bit ones as strings, as JSON numbers cannot hold them
+// without loss.
+json_int!(i32, Serialize::serialize);
+json_int!(u32, Serialize::serialize);
+json_int!(i64, serialize_display);
+json_int!(u64, serialize_display);
+
+struct FloatVisitor;
+
+impl<'de> Visitor<'de> for FloatVisitor {
+    type Value = f64;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a floating point number as number or string")
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+        Ok(value as f64)
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+        Ok(value as f64)
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Self::Value, E> {
+        Ok(value)
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        match value {
+            "NaN" => Ok(f64::NAN),
+            "Infinity" => Ok(f64::INFINITY),
+            "-Infinity" => Ok(f64::NEG_INFINITY),
+            _ => value
+                .parse()
+                .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self)),
+        }
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(0.0)
+    }
+}
+
+fn serialize_float<S: Serializer>(value: f64, serializer: S) -> Result<S::Ok, S::Error> {
+    if value.is_nan() {
+        serializer.serialize_str("NaN")
+    } else if value == f64::INFINITY {
+        serializer.serialize_str("Infinity")
+    } else if value == f64::NEG_INFINITY {
+        serializer.serialize_str("-Infinity")
+    } else {
+        serializer.serialize_f64(value)
+    }
+}
+
+impl JsonScalar for f64 {
+    fn serialize_json<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_float(*self, serializer)
+    }
+
+    fn deserialize_json<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(FloatVisitor)
+    }
+}
+
+impl JsonScalar for f32 {
+    fn serialize_json<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_float(f64::from(*self), serializer)
+    }
+
+    fn deserialize_json<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = deserializer.deserialize_any(FloatVisitor)?;
+        if value.is_finite() && (value < f32::MIN as f64 || value > f32::MAX as f64) {
+            return Err(de::Error::invalid_value(de::Unexpected::Float(value), &"a 32 bit float"));
+        }
+        Ok(value as f32)
+    }
+}
+
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a base64 encoded string")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        // The URL safe alphabet only differs in these two characters, so it is mapped to the
+        // standard one, which also accepts a mix of both.
+        let normalized: String = value
+            .trim_end_matches('=')
+            .chars()
+            .map(|c| match c {
+                '-' => '+',
+                '_' => '/',
+                c => c,
+            })
+            .collect();
+        STANDARD_NO_PAD
+            .decode(normalized)
+            .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+    }
+
+    fn visit_bytes<E: de::Error>(self, value: &[u8]) -> Result<Self::Value, E> {
+        Ok(value.to_vec())
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, value: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(value)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(Vec::new())
+    }
+}
+
+impl JsonScalar for Vec<u8> {
+    fn serialize_json<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(self))
+    }
+
+    fn deserialize_json<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(BytesVisitor)
+    }
+}
+
+impl JsonScalar for prost::bytes::Bytes {
+    fn serialize_json<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(self))
+    }
+
+    fn deserialize_json<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(BytesVisitor).map(Into::into)
+    }
+}
+
+// How a field value is written to and read from JSON: `ScalarCodec` for `JsonScalar` types and
+// `EnumCodec` for the `i32` values prost stores enums as.
+trait Codec<T> {
+    fn serialize<S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error>;
+    fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error>;
+}
+
+struct ScalarCodec;
+
+impl<T: JsonScalar> Codec<T> for ScalarCodec {
+    fn serialize<S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        value.serialize_json(serializer)
+    }
+
+    fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error> {
+        T::deserialize_json(deserializer)
+    }
+}
+
+struct Encode<'a, C, T>(&'a T, PhantomData<C>);
+
+impl<C: Codec<T>, T> Serialize for Encode<'_, C, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        C::serialize(self.0, serializer)
+    }
+}
+
+struct Decode<C, T>(T, PhantomData<C>);
+
+impl<'de, C: Codec<T>, T> Deserialize<'de> for Decode<C, T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        C::deserialize(deserializer).map(|value| Decode(value, PhantomData))
+    }
+}
+
+fn serialize_optional<C: Codec<T>, T, S: Serializer>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => serializer.serialize_some(&Encode::<C, T>(value, PhantomData)),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn deserialize_optional<'de, C: Codec<T>, T, D: Deserializer<'de>>(deserializer: D) -> Result<Option<T>, D::Error> {
+    let value: Option<Decode<C, T>> = Option::deserialize(deserializer)?;
+    Ok(value.map(|value| value.0))
+}
+
+fn serialize_repeated<C: Codec<T>, T, S: Serializer>(values: &[T], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(values.iter().map(|value| Encode::<C, T>(value, PhantomData)))
+}
+
+fn deserialize_repeated<'de, C: Codec<T>, T, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<T>, D::Error> {
+    let values: Option<Vec<Decode<C, T>>> = Option::deserialize(deserializer)?;
+    Ok(values
+        .unwrap_or_default()
+        .into_iter()
+        .map(|value| value.0)
+        .collect())
+}
+
+fn serialize_map<'a, C, M, K, V, S>(map: &'a M, serializer: S) -> Result<S::Ok, S::Error>
+where
+    C: Codec<V>,
+    &'a M: IntoIterator<Item = (&'a K, &'a V)>,
+    K: Serialize + 'a,
+    V: 'a,
+    S: Serializer,
+{
+    serializer.collect_map(
+        map.into_iter()
+            .map(|(key, value)| (key, Encode::<C, V>(value, PhantomData))),
+    )
+}
+
+fn deserialize_map<'de, C, M, K, V, D>(deserializer: D) -> Result<M, D::Error>
+where
+    C: Codec<V>,
+    M: FromIterator<(K, V)>,
+    K: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    let entries: Option<Entries<C, K, V>> = Option::deserialize(deserializer)?;
+    Ok(entries.map(|entries| entries.0).unwrap_or_default().into_iter().collect())
+}
+
+struct Entries<C, K, V>(Vec<(K, V)>, PhantomData<C>);
+
+impl<'de, C: Codec<V>, K: Deserialize<'de>, V> Deserialize<'de> for Entries<C, K, V> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(EntriesVisitor(PhantomData))
+    }
+}
+
+struct EntriesVisitor<C, K, V>(PhantomData<(C, K, V)>);
+
+impl<'de, C: Codec<V>, K: Deserialize<'de>, V> Visitor<'de> for EntriesVisitor<C, K, V> {
+    type Value = Entries<C, K, V>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+        let mut entries = Vec::with_capacity(access.size_hint().unwrap_or(0));
+        while let Some((key, value)) = access.next_entry::<K, Decode<C, V>>()? {
+            entries.push((key, value.0));
+        }
+        Ok(Entries(entries, PhantomData))
+    }
+}
+
+/// `#[serde(with = "::prost_wkt::json::scalar")]` for singular fields, and oneof members.
+pub mod scalar {
+    use super::*;
+
+    pub fn serialize<T: JsonScalar, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        value.serialize_json(serializer)
+    }
+
+    pub fn deserialize<'de, T: JsonScalar, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error> {
+        T::deserialize_json(deserializer)
+    }
+}
+
+/// `#[serde(with = "::prost_wkt::json::optional")]` for optional fields.
+pub mod optional {
+    use super::*;
+
+    pub fn serialize<T: JsonScalar, S: Serializer>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_optional::<ScalarCodec, T, S>(value, serializer)
+    }
+
+    pub fn deserialize<'de, T: JsonScalar, D: Deserializer<'de>>(deserializer: D) -> Result<Option<T>, D::Error> {
+        deserialize_optional::<ScalarCodec, T, D>(deserializer)
+    }
+}
+
+/// `#[serde(with = "::prost_wkt::json::repeated")]` for repeated fields.
+pub mod repeated {
+    use super::*;
+
+    pub fn serialize<T: JsonScalar, S: Serializer>(values: &[T], serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_repeated::<ScalarCodec, T, S>(values, serializer)
+    }
+
+    pub fn deserialize<'de, T: JsonScalar, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<T>, D::Error> {
+        deserialize_repeated::<ScalarCodec, T, D>(deserializer)
+    }
+}
+
+/// `#[serde(with = "::prost_wkt::json::map")]` for map fields with 64 bit integer, floating point
+/// or bytes values.
+/// Works with both `HashMap` and `BTreeMap`.
+pub mod map {
+    use super::*;
+
+    pub fn serialize<'a, M, K, V, S>(map: &'a M, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        &'a M: IntoIterator<Item = (&'a K, &'a V)>,
+        K: Serialize + 'a,
+        V: JsonScalar + 'a,
+        S: Serializer,
+    {
+        serialize_map::<ScalarCodec, M, K, V, S>(map, serializer)
+    }
+
+    pub fn deserialize<'de, M, K, V, D>(deserializer: D) -> Result<M, D::Error>
+    where
+        M: FromIterator<(K, V)>,
+        K: Deserialize<'de>,
+        V: JsonScalar,
+        D: Deserializer<'de>,
+    {
+        deserialize_map::<ScalarCodec, M, K, V, D>(deserializer)
+    }
+}
+
+/// A protobuf enum generated by prost, which stores enum fields as `i32`. `prost-wkt-build`
+/// implements it through `proto_enum_serde!` for every enum it generates serde helpers for.
+pub trait ProtoEnum {
+    /// The fully qualified name of the enum, e.g. `my.package.MyEnum`.
+    const NAME: &'static str;
+
+    /// Returns the proto name of the value with the given number, if there is one.
+    fn name_of(value: i32) -> Option<&'static str>;
+
+    /// Returns the number of the value with the given proto name, if there is one.
+    fn value_of(name: &str) -> Option<i32>;
+}
+
+struct EnumCodec<E>(PhantomData<E>);
+
+impl<E: ProtoEnum> Codec<i32> for EnumCodec<E> {
+    fn serialize<S: Serializer>(value: &i32, serializer: S) -> Result<S::Ok, S::Error> {
+        // Values unknown to this version of the enum are kept as numbers.
+        match E::name_of(*value) {
+            Some(name) => serializer.serialize_str(name),
+            None => serializer.serialize_i32(*value),
+        }
+    }
+
+    fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i32, D::Error> {
+        deserializer.deserialize_any(EnumVisitor::<E>(PhantomData))
+    }
+}
+
+struct EnumVisitor<E>(PhantomData<E>);
+
+impl<'de, E: ProtoEnum> Visitor<'de> for EnumVisitor<E> {
+    type Value = i32;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a value name or number of enum {}", E::NAME)
+    }
+
+    fn visit_i64<Err: de::Error>(self, value: i64) -> Result<Self::Value, Err> {
+        i32::try_from(value).map_err(|_| Err::invalid_value(de::Unexpected::Signed(value), &self))
+    }
+
+    fn visit_u64<Err: de::Error>(self, value: u64) -> Result<Self::Value, Err> {
+        i32::try_from(value).map_err(|_| Err::invalid_value(de::Unexpected::Unsigned(value), &self))
+    }
+
+    fn visit_str<Err: de::Error>(self, value: &str) -> Result<Self::Value, Err> {
+        E::value_of(value)
+            .ok_or_else(|| Err::custom(format_args!("unknown value \"{value}\" of enum {}", E::NAME)))
+    }
+
+    fn visit_unit<Err: de::Error>(self) -> Result<Self::Value, Err> {
+        Ok(0)
+    }
+}
+
+/// Helpers for enum fields, used by the modules `proto_enum_serde!` generates for an enum: values
+/// are written as their proto name, and read from either their name or their number.
+pub mod enumeration {
+    use super::*;
+
+    pub fn serialize<E: ProtoEnum, S: Serializer>(value: &i32, serializer: S) -> Result<S::Ok, S::Error> {
+        EnumCodec::<E>::serialize(value, serializer)
+    }
+
+    pub fn deserialize<'de, E: ProtoEnum, D: Deserializer<'de>>(deserializer: D) -> Result<i32, D::Error> {
+        EnumCodec::<E>::deserialize(deserializer)
+    }
+
+    pub fn serialize_optional<E: ProtoEnum, S: Serializer>(value: &Option<i32>, serializer: S) -> Result<S::Ok, S::Error> {
+        super::serialize_optional::<EnumCodec<E>, i32, S>(value, serializer)
+    }
+
+    pub fn deserialize_optional<'de, E: ProtoEnum, D: Deserializer<'de>>(deserializer: D) -> Result<Option<i32>, D::Error> {
+        super::deserialize_optional::<EnumCodec<E>, i32, D>(deserializer)
+    }
+
+    pub fn serialize_repeated<E: ProtoEnum, S: Serializer>(values: &[i32], serializer: S) -> Result<S::Ok, S::Error> {
+        super::serialize_repeated::<EnumCodec<E>, i32, S>(values, serializer)
+    }
+
+    pub fn deserialize_repeated<'de, E: ProtoEnum, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<i32>, D::Error> {
+        super::deserialize_repeated::<EnumCodec<E>, i32, D>(deserializer)
+    }
+
+    pub fn serialize_map<'a, E, M, K, S>(map: &'a M, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        E: ProtoEnum,
+        &'a M: IntoIterator<Item = (&'a K, &'a i32)>,
+        K: Serialize + 'a,
+        S: Serializer,
+    {
+        super::serialize_map::<EnumCodec<E>, M, K, i32, S>(map, serializer)
+    }
+
+    pub fn deserialize_map<'de, E, M, K, D>(deserializer: D) -> Result<M, D::Error>
+    where
+        E: ProtoEnum,
+        M: FromIterator<(K, i32)>,
+        K: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        super::deserialize_map::<EnumCodec<E>, M, K, i32, D>(deserializer)
+    }
+}
+
+/// Generates the serde helper modules for an enum generated by prost: invoked in a module of its
+/// own, that module can be used with `#[serde(with = "...")]` on singular enum fields, and its
+/// `optional`, `repeated` and `map` submodules on the corresponding fields.
+///
+/// ```ignore
+/// pub mod color {
+///     ::prost_wkt::proto_enum_serde!(super::Color, "my.package.Color");
+/// }
+/// ```
+#[macro_export]
+macro_rules! proto_enum_serde {
+    ($enum:ty, $name:expr) => {
+        type Enum = $enum;
+
+        impl $crate::json::ProtoEnum for Enum {
+            const NAME: &'static str = $name;
+
+            fn name_of(value: i32) -> ::core::option::Option<&'static str> {
+                Self::from_i32(value).map(|value| value.as_str_name())
+            }
+
+            fn value_of(name: &str) -> ::core::option::Option<i32> {
+                Self::from_str_name(name).map(|value| value as i32)
+            }
+        }
+
+        pub fn serialize<S: $crate::serde::Serializer>(
+            value: &i32,
+            serializer: S,
+        ) -> ::core::result::Result<S::Ok, S::Error> {
+            $crate::json::enumeration::serialize::<Enum, S>(value, serializer)
+        }
+
+        pub fn deserialize<'de, D: $crate::serde::Deserializer<'de>>(
+            deserializer: D,
+        ) -> ::core::result::Result<i32, D::Error> {
+            $crate::json::enumeration::deserialize::<Enum, D>(deserializer)
+        }
+
+        pub mod optional {
+            pub fn serialize<S: $crate::serde::Serializer>(
+                value: &::core::option::Option<i32>,
+                serializer: S,
+            ) -> ::core::result::Result<S::Ok, S::Error> {
+                $crate::json::enumeration::serialize_optional::<super::Enum, S>(value, serializer)
+            }
+
+            pub fn deserialize<'de, D: $crate::serde::Deserializer<'de>>(
+                deserializer: D,
+            ) -> ::core::result::Result<::core::option::Option<i32>, D::Error> {
+                $crate::json::enumeration::deserialize_optional::<super::Enum, D>(deserializer)
+            }
+        }
+
+        pub mod repeated {
+            pub fn serialize<S: $crate::serde::Serializer>(
+                values: &[i32],
+                serializer: S,
+            ) -> ::core::result::Result<S::Ok, S::Error> {
+                $crate::json::enumeration::serialize_repeated::<super::Enum, S>(values, serializer)
+            }
+
+            pub fn deserialize<'de, D: $crate::serde::Deserializer<'de>>(
+                deserializer: D,
+            ) -> ::core::result::Result<::std::vec::Vec<i32>, D::Error> {
+                $crate::json::enumeration::deserialize_repeated::<super::Enum, D>(deserializer)
+            }
+        }
+
+        pub mod map {
+            pub fn serialize<'a, M, K, S>(map: &'a M, serializer: S) -> ::core::result::Result<S::Ok, S::Error>
+            where
+                &'a M: ::core::iter::IntoIterator<Item = (&'a K, &'a i32)>,
+                K: $crate::serde::Serialize + 'a,
+                S: $crate::serde::Serializer,
+            {
+                $crate::json::enumeration::serialize_map::<super::Enum, M, K, S>(map, serializer)
+            }
+
+            pub fn deserialize<'de, M, K, D>(deserializer: D) -> ::core::result::Result<M, D::Error>
+            where
+                M: ::core::iter::FromIterator<(K, i32)>,
+                K: $crate::serde::Deserialize<'de>,
+                D: $crate::serde::Deserializer<'de>,
+            {
+                $crate::json::enumeration::deserialize_map::<super::Enum, M, K, D>(deserializer)
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int<T: JsonScalar>(json: &str) -> Option<T> {
+        T::deserialize_json(&mut serde_json::Deserializer::from_str(json)).ok()
+    }
+
+    #[test]
+    fn deserialize_ints() {
+        assert_eq!(int::<i64>("\"-9223372036854775808\""), Some(i64::MIN));
+        assert_eq!(int::<i64>("-9.223372036854775808e18"), Some(i64::MIN));
+        assert_eq!(int::<i64>("1e2"), Some(100));
+        assert_eq!(int::<u64>("null"), Some(0));
+        assert_eq!(int::<u64>("1e19"), Some(10_000_000_000_000_000_000));
+
+        // `9223372036854775807.0` is 2^63 as a float, which is out of range, like `1e19`.
+        assert_eq!(int::<i64>("9223372036854775807.0"), None);
+        assert_eq!(int::<i64>("1e19"), None);
+        assert_eq!(int::<u64>("1.8446744073709552e19"), None);
+        assert_eq!(int::<u64>("-1"), None);
+        assert_eq!(int::<i64>("1.5"), None);
+        assert_eq!(int::<i32>("2147483648"), None);
+        assert_eq!(int::<u32>("\"7\""), Some(7));
+    }
+
+    #[test]
+    fn deserialize_bytes() {
+        let bytes = |json: &str| Vec::<u8>::deserialize_json(&mut serde_json::Deserializer::from_str(json)).ok();
+        assert_eq!(bytes("\"+/8B\""), Some(vec![0xfb, 0xff, 0x01]));
+        assert_eq!(bytes("\"-_8B\""), Some(vec![0xfb, 0xff, 0x01]));
+        assert_eq!(bytes("\"+_8B\""), Some(vec![0xfb, 0xff, 0x01]));
+        assert_eq!(bytes("\"aGk=\""), Some(b"hi".to_vec()));
+        assert_eq!(bytes("\"!!\""), None);
+    }
+}
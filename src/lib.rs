@@ -1,7 +1,20 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
 pub use inventory;
 
 pub use typetag;
 
+pub use serde;
+
+mod fieldmask;
+pub use crate::fieldmask::*;
+
+mod registry;
+pub use crate::registry::*;
+
+pub mod json;
+
 /// Trait to support serialization and deserialization of `prost` messages.
 #[typetag::serde(tag = "@type")]
 pub trait MessageSerde: prost::Message + std::any::Any {
@@ -85,7 +98,7 @@ impl dyn MessageSerde {
     }
 }
 
-type MessageSerdeDecoderFn = fn(&[u8]) -> Result<Box<dyn MessageSerde>, ::prost::DecodeError>;
+pub type MessageSerdeDecoderFn = fn(&[u8]) -> Result<Box<dyn MessageSerde>, ::prost::DecodeError>;
 
 pub struct MessageSerdeDecoderEntry {
     pub type_url: &'static str,
@@ -93,3 +106,30 @@ pub struct MessageSerdeDecoderEntry {
 }
 
 inventory::collect!(MessageSerdeDecoderEntry);
+
+/// Returns the fully qualified message name of a type URL, i.e. everything after the last `/`.
+/// `type.googleapis.com/my.package.MyMessage`, `type.example.com/my.package.MyMessage` and
+/// `/my.package.MyMessage` all name `my.package.MyMessage`.
+pub fn type_name_from_url(type_url: &str) -> &str {
+    type_url.rsplit_once('/').map_or(type_url, |(_, name)| name)
+}
+
+/// Finds the registered decoder for a type URL. Entries are matched on the fully qualified
+/// message name, so the prefix of `type_url` does not need to match the registered one.
+///
+/// The entries are indexed by name the first time this is called, so lookups do not depend on
+/// the number of registered messages. If several entries share a message name, e.g. when the same
+/// message is registered with different prefixes, the first one in `inventory` order is returned.
+pub fn find_decoder(type_url: &str) -> Option<&'static MessageSerdeDecoderEntry> {
+    static REGISTRY: OnceLock<HashMap<&'static str, &'static MessageSerdeDecoderEntry>> = OnceLock::new();
+    REGISTRY
+        .get_or_init(|| {
+            let mut entries = HashMap::new();
+            for entry in inventory::iter::<MessageSerdeDecoderEntry> {
+                entries.entry(type_name_from_url(entry.type_url)).or_insert(entry);
+            }
+            entries
+        })
+        .get(type_name_from_url(type_url))
+        .copied()
+}
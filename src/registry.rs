@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+
+use crate::{type_name_from_url, MessageSerde, MessageSerdeDecoderFn, MessageSerdeDecoderEntry};
+
+type MessageSerdeJsonFn = fn(&serde_json::Value) -> Result<Box<dyn MessageSerde>, serde_json::Error>;
+
+#[derive(Clone)]
+struct RegistryEntry {
+    decoder: MessageSerdeDecoderFn,
+    from_json: Option<MessageSerdeJsonFn>,
+}
+
+/// An explicit set of message types that `Any` values can be unpacked to, for use instead of, or
+/// on top of, the types registered at compile time through `inventory`.
+///
+/// Like the global registry, types are matched on the fully qualified message name after the last
+/// `/` of the type URL. A registry created with `TypeRegistry::scoped` falls back to its parent
+/// for the types it does not know about itself.
+#[derive(Clone, Default)]
+pub struct TypeRegistry {
+    entries: HashMap<String, RegistryEntry>,
+    parent: Option<Arc<TypeRegistry>>,
+}
+
+impl TypeRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a registry containing all message types registered at compile time.
+    pub fn from_inventory() -> Self {
+        let mut registry = Self::new();
+        for entry in inventory::iter::<MessageSerdeDecoderEntry> {
+            registry.register_decoder(entry.type_url, entry.decoder);
+        }
+        registry
+    }
+
+    /// Creates an empty registry that falls back to `parent` for the types it does not know
+    /// about. Types registered in the scoped registry take precedence over those of `parent`.
+    pub fn scoped(parent: Arc<TypeRegistry>) -> Self {
+        TypeRegistry {
+            entries: HashMap::new(),
+            parent: Some(parent),
+        }
+    }
+
+    /// Registers the message type `M` under its type URL.
+    pub fn register<M>(&mut self) -> &mut Self
+    where
+        M: MessageSerde + Default + DeserializeOwned,
+    {
+        let type_url = M::default().type_url();
+        self.insert(
+            type_url,
+            RegistryEntry {
+                decoder: decode::<M>,
+                from_json: Some(from_json::<M>),
+            },
+        )
+    }
+
+    /// Registers a decoder for the given type URL. Messages registered this way can be unpacked
+    /// with the registry, but are deserialized from JSON through their `MessageSerde`
+    /// implementation.
+    pub fn register_decoder(&mut self, type_url: &str, decoder: MessageSerdeDecoderFn) -> &mut Self {
+        self.insert(
+            type_url,
+            RegistryEntry {
+                decoder,
+                from_json: None,
+            },
+        )
+    }
+
+    /// Returns `true` if the registry, or one of its parents, knows the type URL.
+    pub fn contains(&self, type_url: &str) -> bool {
+        self.get(type_url).is_some()
+    }
+
+    /// Decodes `buf` as the message type registered for `type_url`, or returns `None` if the type
+    /// is unknown.
+    pub fn decode(&self, type_url: &str, buf: &[u8]) -> Option<Result<Box<dyn MessageSerde>, prost::DecodeError>> {
+        self.get(type_url).map(|entry| (entry.decoder)(buf))
+    }
+
+    /// Deserializes the JSON fields of the message type registered for `type_url`, without the
+    /// `@type` field. Returns `None` if the type is unknown or was registered with
+    /// `register_decoder`.
+    pub fn from_json(
+        &self,
+        type_url: &str,
+        json: &serde_json::Value,
+    ) -> Option<Result<Box<dyn MessageSerde>, serde_json::Error>> {
+        let from_json = self.get(type_url)?.from_json?;
+        Some(from_json(json))
+    }
+
+    fn insert(&mut self, type_url: &str, entry: RegistryEntry) -> &mut Self {
+        self.entries
+            .insert(type_name_from_url(type_url).to_string(), entry);
+        self
+    }
+
+    fn get(&self, type_url: &str) -> Option<&RegistryEntry> {
+        self.entries
+            .get(type_name_from_url(type_url))
+            .or_else(|| self.parent.as_ref()?.get(type_url))
+    }
+}
+
+impl std::fmt::Debug for TypeRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TypeRegistry")
+            .field("types", &self.entries.keys().collect::<Vec<_>>())
+            .field("parent", &self.parent)
+            .finish()
+    }
+}
+
+fn decode<M: MessageSerde + Default>(buf: &[u8]) -> Result<Box<dyn MessageSerde>, prost::DecodeError> {
+    let message: M = prost::Message::decode(buf)?;
+    Ok(Box::new(message))
+}
+
+fn from_json<M: MessageSerde + DeserializeOwned>(
+    json: &serde_json::Value,
+) -> Result<Box<dyn MessageSerde>, serde_json::Error> {
+    let message = M::deserialize(json)?;
+    Ok(Box::new(message))
+}
@@ -0,0 +1,281 @@
+use std::ffi::OsString;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use prost::Message;
+use prost_types::FileDescriptorSet;
+
+use crate::{add_json_attributes, try_add_serde_with_options, SerdeOptions};
+
+// The types provided by `prost-wkt-types`, with the Rust path they are exported under. The
+// remaining `google.protobuf` types, i.e. those of `descriptor.proto`, are mapped to `prost-types`
+// like prost does by default.
+const WELL_KNOWN_TYPES: &[(&str, &str)] = &[
+    (".google.protobuf.Any", "::prost_wkt_types::Any"),
+    (".google.protobuf.Timestamp", "::prost_wkt_types::Timestamp"),
+    (".google.protobuf.Duration", "::prost_wkt_types::Duration"),
+    (".google.protobuf.Struct", "::prost_wkt_types::Struct"),
+    (".google.protobuf.Value", "::prost_wkt_types::Value"),
+    (".google.protobuf.ListValue", "::prost_wkt_types::ListValue"),
+    (".google.protobuf.NullValue", "::prost_wkt_types::NullValue"),
+    (".google.protobuf.FieldMask", "::prost_wkt_types::FieldMask"),
+    (".google.protobuf.Type", "::prost_wkt_types::Type"),
+    (".google.protobuf.Field", "::prost_wkt_types::Field"),
+    (".google.protobuf.Enum", "::prost_wkt_types::Enum"),
+    (".google.protobuf.EnumValue", "::prost_wkt_types::EnumValue"),
+    (".google.protobuf.Option", "::prost_wkt_types::ProtoOption"),
+    (".google.protobuf.Syntax", "::prost_wkt_types::Syntax"),
+    (".google.protobuf.SourceContext", "::prost_wkt_types::SourceContext"),
+    (".google.protobuf.Api", "::prost_wkt_types::Api"),
+    (".google.protobuf.Method", "::prost_wkt_types::Method"),
+    (".google.protobuf.Mixin", "::prost_wkt_types::Mixin"),
+    (".google.protobuf.Empty", "::prost_wkt_types::Empty"),
+    (".google.protobuf.BoolValue", "::prost_wkt_types::BoolValue"),
+    (".google.protobuf.BytesValue", "::prost_wkt_types::BytesValue"),
+    (".google.protobuf.DoubleValue", "::prost_wkt_types::DoubleValue"),
+    (".google.protobuf.FloatValue", "::prost_wkt_types::FloatValue"),
+    (".google.protobuf.Int32Value", "::prost_wkt_types::Int32Value"),
+    (".google.protobuf.Int64Value", "::prost_wkt_types::Int64Value"),
+    (".google.protobuf.StringValue", "::prost_wkt_types::StringValue"),
+    (".google.protobuf.UInt32Value", "::prost_wkt_types::UInt32Value"),
+    (".google.protobuf.UInt64Value", "::prost_wkt_types::UInt64Value"),
+];
+
+const MESSAGE_SERDE_ATTRIBUTE: &str = "#[derive(::serde::Serialize, ::serde::Deserialize)] #[serde(default, rename_all = \"camelCase\")]";
+const ENUM_SERDE_ATTRIBUTE: &str = "#[derive(::serde::Serialize, ::serde::Deserialize)] #[serde(rename_all = \"camelCase\")]";
+
+/// Builder that compiles protos with `prost_build` and adds the `prost-wkt` implementations in one
+/// go. It replaces the boilerplate of setting a descriptor set path, mapping the well known types
+/// to `prost-wkt-types`, deriving serde for all messages following the proto3 JSON mapping (see
+/// `add_json_attributes`) and calling `add_serde` afterwards:
+///
+/// ```ignore
+/// prost_wkt_build::Config::new()
+///     .compile_protos(&["proto/messages.proto"], &["proto/"])
+///     .unwrap();
+/// ```
+///
+/// The generated code derives `::serde::Serialize` and `::serde::Deserialize`, so the crate
+/// including it needs a dependency on `serde` with the `derive` feature.
+#[derive(Debug)]
+pub struct Config {
+    prost: prost_build::Config,
+    options: SerdeOptions,
+    out_dir: Option<PathBuf>,
+    file_descriptor_set_path: Option<PathBuf>,
+    protoc_args: Vec<OsString>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config::new()
+    }
+}
+
+impl Config {
+    /// Creates a builder that maps the well known types to `prost-wkt-types`, derives serde for
+    /// all generated messages and enums and generates `FieldMaskable` implementations, see
+    /// `SerdeOptions::field_masks`.
+    pub fn new() -> Self {
+        let mut config = Config {
+            prost: prost_build::Config::new(),
+            options: SerdeOptions::default().field_masks(),
+            out_dir: None,
+            file_descriptor_set_path: None,
+            protoc_args: Vec::new(),
+        };
+        // Prost's own mapping of the well known types cannot be overridden, as it rejects duplicate
+        // extern paths, so it is turned off and replaced. The wrapper types are mapped to the ones
+        // of `prost-wkt-types` rather than to primitives, so they are written following the proto3
+        // JSON mapping as well, e.g. an `Int64Value` as a string.
+        for (proto_path, rust_path) in WELL_KNOWN_TYPES {
+            config.extern_path(*proto_path, *rust_path);
+        }
+        config
+            .prost
+            .compile_well_known_types()
+            .extern_path(".google.protobuf", "::prost_types")
+            .message_attribute(".", MESSAGE_SERDE_ATTRIBUTE)
+            .enum_attribute(".", ENUM_SERDE_ATTRIBUTE);
+        config
+    }
+
+    /// The wrapped `prost_build::Config`, to set any other prost option. Extern paths and protoc
+    /// arguments should be added through `Config::extern_path` and `Config::protoc_arg` instead.
+    pub fn prost_config(&mut self) -> &mut prost_build::Config {
+        &mut self.prost
+    }
+
+    /// See `prost_build::Config::extern_path`.
+    pub fn extern_path<P1, P2>(&mut self, proto_path: P1, rust_path: P2) -> &mut Self
+    where
+        P1: Into<String>,
+        P2: Into<String>,
+    {
+        let (proto_path, rust_path) = (proto_path.into(), rust_path.into());
+        self.prost.extern_path(proto_path.clone(), rust_path.clone());
+        self.options = self.options.clone().extern_path(proto_path, rust_path);
+        self
+    }
+
+    /// See `SerdeOptions::type_url_prefix`.
+    pub fn type_url_prefix<S: Into<String>>(&mut self, prefix: S) -> &mut Self {
+        self.options = self.options.clone().type_url_prefix(prefix);
+        self
+    }
+
+    /// See `prost_build::Config::out_dir`. Defaults to the `OUT_DIR` environment variable.
+    pub fn out_dir<P: Into<PathBuf>>(&mut self, path: P) -> &mut Self {
+        self.out_dir = Some(path.into());
+        self
+    }
+
+    /// See `prost_build::Config::file_descriptor_set_path`. Defaults to `descriptors.bin` in the
+    /// output directory.
+    pub fn file_descriptor_set_path<P: Into<PathBuf>>(&mut self, path: P) -> &mut Self {
+        self.file_descriptor_set_path = Some(path.into());
+        self
+    }
+
+    /// See `prost_build::Config::protoc_arg`.
+    pub fn protoc_arg<S: Into<OsString>>(&mut self, arg: S) -> &mut Self {
+        self.protoc_args.push(arg.into());
+        self
+    }
+
+    /// Compiles the protos with protoc, see `prost_build::Config::compile_protos`, and generates
+    /// the code with `Config::compile_fds`.
+    pub fn compile_protos(
+        &mut self,
+        protos: &[impl AsRef<Path>],
+        includes: &[impl AsRef<Path>],
+    ) -> Result<()> {
+        let descriptor_path = self.resolve_file_descriptor_set_path()?;
+        let protoc = prost_build::protoc_from_env();
+        let mut cmd = Command::new(&protoc);
+        cmd.arg("--include_imports")
+            .arg("--include_source_info")
+            .arg("-o")
+            .arg(&descriptor_path);
+        for include in includes.iter().filter(|include| include.as_ref().exists()) {
+            cmd.arg("-I").arg(include.as_ref());
+        }
+        if let Some(protoc_include) = prost_build::protoc_include_from_env() {
+            cmd.arg("-I").arg(protoc_include);
+        }
+        cmd.args(&self.protoc_args);
+        for proto in protos {
+            cmd.arg(proto.as_ref());
+        }
+
+        let output = cmd.output().map_err(|err| {
+            Error::new(err.kind(), format!("failed to invoke protoc (path: {protoc:?}): {err}"))
+        })?;
+        if !output.status.success() {
+            return Err(Error::other(format!(
+                "protoc failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+
+        let descriptor_bytes = std::fs::read(descriptor_path)?;
+        let descriptor = FileDescriptorSet::decode(&descriptor_bytes[..])
+            .map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+        self.compile_fds(descriptor)
+    }
+
+    /// Like `Config::compile_protos`, but parses the protos with `protox` in pure Rust instead of
+    /// running protoc, so no protoc installation is needed. The well known types can be imported
+    /// without an include for them. Arguments added with `Config::protoc_arg` are ignored.
+    #[cfg(feature = "vendored-protox")]
+    pub fn compile_with_protox(
+        &mut self,
+        protos: &[impl AsRef<Path>],
+        includes: &[impl AsRef<Path>],
+    ) -> Result<()> {
+        let descriptor_path = self.resolve_file_descriptor_set_path()?;
+        let includes = includes.iter().filter(|include| include.as_ref().exists());
+        let descriptor = protox::compile(protos, includes)
+            .map_err(|err| Error::other(format!("protox failed: {err}")))?;
+        std::fs::write(descriptor_path, descriptor.encode_to_vec())?;
+        self.compile_fds(descriptor)
+    }
+
+    /// Generates the code for an already compiled descriptor set with prost, see
+    /// `prost_build::Config::compile_fds`, and adds the `prost-wkt` implementations to it. Errors of
+    /// `add_json_attributes` and `try_add_serde` are returned as `std::io::Error`s wrapping the
+    /// `prost_wkt_build::Error`.
+    pub fn compile_fds(&mut self, descriptor: FileDescriptorSet) -> Result<()> {
+        let out_dir = self.resolve_out_dir()?;
+        add_json_attributes(&mut self.prost, &descriptor, &self.options).map_err(Error::other)?;
+        self.prost.out_dir(&out_dir).compile_fds(descriptor.clone())?;
+        try_add_serde_with_options(out_dir, descriptor, &self.options).map_err(Error::other)
+    }
+
+    fn resolve_file_descriptor_set_path(&self) -> Result<PathBuf> {
+        match &self.file_descriptor_set_path {
+            Some(path) => Ok(path.clone()),
+            None => Ok(self.resolve_out_dir()?.join("descriptors.bin")),
+        }
+    }
+
+    fn resolve_out_dir(&self) -> Result<PathBuf> {
+        match &self.out_dir {
+            Some(out_dir) => Ok(out_dir.clone()),
+            None => std::env::var_os("OUT_DIR").map(PathBuf::from).ok_or_else(|| {
+                Error::other("OUT_DIR environment variable is not set")
+            }),
+        }
+    }
+}
+
+/// Compiles the protos with `Config::compile_with_protox` using the default configuration, without
+/// needing protoc:
+///
+/// ```ignore
+/// prost_wkt_build::compile_with_protox(&["proto/messages.proto"], &["proto/"]).unwrap();
+/// ```
+#[cfg(feature = "vendored-protox")]
+pub fn compile_with_protox(protos: &[impl AsRef<Path>], includes: &[impl AsRef<Path>]) -> Result<()> {
+    Config::new().compile_with_protox(protos, includes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every well known type `prost-wkt-types` generates must be mapped to it, as the catch-all
+    // mapping to `prost_types` would otherwise pick it up.
+    #[test]
+    fn well_known_types_are_mapped_to_prost_wkt_types() {
+        let proto_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../wkt-types/proto");
+        let out = std::env::temp_dir().join(format!("prost-wkt-build-{}-wkt.bin", std::process::id()));
+        let mut cmd = Command::new(prost_build::protoc_from_env());
+        cmd.arg("--include_imports").arg("-o").arg(&out).arg("-I").arg(&proto_dir);
+        if let Some(protoc_include) = prost_build::protoc_include_from_env() {
+            cmd.arg("-I").arg(protoc_include);
+        }
+        for proto in ["pbany", "pbempty", "pbmask", "pbstruct", "pbtime", "pbtype", "pbwrappers"] {
+            cmd.arg(proto_dir.join(format!("{proto}.proto")));
+        }
+        let output = cmd.output().unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let descriptor = FileDescriptorSet::decode(&std::fs::read(&out).unwrap()[..]).unwrap();
+        let _ = std::fs::remove_file(&out);
+
+        let mut names = Vec::new();
+        for fd in descriptor.file.iter().filter(|fd| fd.package() == "google.protobuf") {
+            names.extend(fd.message_type.iter().map(|msg| msg.name()));
+            names.extend(fd.enum_type.iter().map(|enum_type| enum_type.name()));
+        }
+        assert!(names.contains(&"Timestamp"));
+        for name in names {
+            let proto_path = format!(".google.protobuf.{name}");
+            assert!(
+                WELL_KNOWN_TYPES.iter().any(|(path, _)| *path == proto_path),
+                "{proto_path} is not mapped to prost-wkt-types"
+            );
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::path::PathBuf;
+
+/// Error returned by `try_add_serde`, `try_add_serde_with_options` and `add_json_attributes`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The code generated for the given proto files could not be written to `path`.
+    Io {
+        proto_files: Vec<String>,
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// Messages in two proto files are registered with the same type URL, which would make the
+    /// registration of one of them fail at runtime.
+    DuplicateTypeUrl {
+        type_url: String,
+        proto_file: String,
+        other_proto_file: String,
+    },
+    /// Two types are generated with the same Rust identifier in the same module, e.g. the
+    /// messages `foo_bar` and `FooBar`, which are both generated as `FooBar`.
+    IdentCollision {
+        rust_path: String,
+        proto_name: String,
+        proto_file: String,
+        other_proto_name: String,
+        other_proto_file: String,
+    },
+    /// A oneof cannot be written as fields of its message, as prost matches the attribute that
+    /// flattens it by a path suffix, which matches another field as well, e.g. for the oneof
+    /// `pkg.Msg.choice` and the field `other.pkg.Msg.choice`.
+    OneofCollision {
+        oneof: String,
+        proto_file: String,
+        other_path: String,
+        other_proto_file: String,
+    },
+}
+
+impl Error {
+    /// Returns the proto file the error is about, if any. For collisions, this is the file of the
+    /// type that comes last in the descriptor set.
+    pub fn proto_file(&self) -> Option<&str> {
+        match self {
+            Error::Io { proto_files, .. } => proto_files.first().map(String::as_str),
+            Error::DuplicateTypeUrl { proto_file, .. }
+            | Error::IdentCollision { proto_file, .. }
+            | Error::OneofCollision { proto_file, .. } => Some(proto_file),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io {
+                proto_files,
+                path,
+                source,
+            } => write!(
+                f,
+                "failed to write {} (generated from {}): {source}",
+                path.display(),
+                proto_files.join(", ")
+            ),
+            Error::DuplicateTypeUrl {
+                type_url,
+                proto_file,
+                other_proto_file,
+            } => write!(
+                f,
+                "type URL {type_url} of a message in {proto_file} is already used by a message in {other_proto_file}"
+            ),
+            Error::IdentCollision {
+                rust_path,
+                proto_name,
+                proto_file,
+                other_proto_name,
+                other_proto_file,
+            } => write!(
+                f,
+                "{proto_name} in {proto_file} and {other_proto_name} in {other_proto_file} are both generated as {rust_path}"
+            ),
+            Error::OneofCollision {
+                oneof,
+                proto_file,
+                other_path,
+                other_proto_file,
+            } => write!(
+                f,
+                "oneof {oneof} in {proto_file} cannot be flattened into its message, as the attribute would apply to {other_path} in {other_proto_file} as well"
+            ),
+        }
+    }
+}
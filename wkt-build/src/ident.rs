@@ -0,0 +1,54 @@
+//! The identifier conversions of `prost_build`, whose `ident` module is private. The generated code
+//! has to refer to the types and fields exactly as prost named them, so these must be kept in sync
+//! with the version of prost-build in use, including the version of `heck`.
+
+use heck::{ToSnakeCase, ToUpperCamelCase};
+use proc_macro2::{Ident, Span};
+
+/// Converts a proto name to the `lower_snake` case identifier prost uses for fields and modules.
+/// Keywords are escaped as raw identifiers, except for those that cannot be raw, which get a `_`
+/// suffix instead.
+pub(crate) fn to_snake(s: &str) -> String {
+    let mut ident = s.to_snake_case();
+    match ident.as_str() {
+        "as" | "break" | "const" | "continue" | "else" | "enum" | "false" | "fn" | "for" | "if"
+        | "impl" | "in" | "let" | "loop" | "match" | "mod" | "move" | "mut" | "pub" | "ref"
+        | "return" | "static" | "struct" | "trait" | "true" | "type" | "unsafe" | "use" | "where"
+        | "while" | "dyn" | "abstract" | "become" | "box" | "do" | "final" | "macro" | "override"
+        | "priv" | "typeof" | "unsized" | "virtual" | "yield" | "async" | "await" | "try" => {
+            ident.insert_str(0, "r#")
+        }
+        "self" | "super" | "extern" | "crate" => ident += "_",
+        _ => (),
+    }
+    ident
+}
+
+/// Converts a proto name to the `UpperCamel` case identifier prost uses for types and enum
+/// variants. `Self` cannot be a raw identifier, so it gets a `_` suffix.
+pub(crate) fn to_upper_camel(s: &str) -> String {
+    let mut ident = s.to_upper_camel_case();
+    if ident == "Self" {
+        ident += "_";
+    }
+    ident
+}
+
+/// The identifier of the field or module prost generates for a proto name, see `to_snake`.
+pub(crate) fn to_field_ident(name: &str) -> Ident {
+    to_ident(&to_snake(name))
+}
+
+/// The identifier of the type or enum variant prost generates for a proto name, see
+/// `to_upper_camel`.
+pub(crate) fn to_type_ident(name: &str) -> Ident {
+    to_ident(&to_upper_camel(name))
+}
+
+fn to_ident(ident: &str) -> Ident {
+    match ident.strip_prefix("r#") {
+        Some(raw) => Ident::new_raw(raw, Span::call_site()),
+        None => Ident::new(ident, Span::call_site()),
+    }
+}
+
@@ -0,0 +1,400 @@
+use std::collections::HashMap;
+
+use heck::ToSnakeCase;
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+
+use prost_build::Module;
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{DescriptorProto, EnumDescriptorProto, FieldDescriptorProto, FileDescriptorSet};
+
+use crate::ident::{to_field_ident, to_type_ident};
+use crate::{Error, SerdeOptions};
+
+// Name of the module `add_serde` generates the enum helper modules into, at the root of the
+// generated file of every package.
+const ENUM_SERDE_MODULE: &str = "prost_wkt_enum_serde";
+
+/// Adds serde field attributes to `config` that make the derived `Serialize` and `Deserialize`
+/// implementations follow the proto3 JSON mapping: fields are named by their `json_name`, also
+/// accepting the original proto field name on input, fields with default values are omitted, and
+/// 64 bit integers, non-finite floats and bytes are written as strings using the helpers in
+/// `prost_wkt::json`. Enum fields are written as the name of their value, using the helper modules
+/// `add_serde` generates for every enum; enums of extern types are kept as numbers. The set member
+/// of a oneof is written as a field of the message itself.
+///
+/// Must be called before compiling `descriptor` with `config`, which should derive serde for the
+/// messages itself, e.g. with `message_attribute`. `prost_wkt_build::Config` does both.
+///
+/// # Errors
+///
+/// Returns `Error::OneofCollision`, without adding any attributes, if a oneof cannot be flattened
+/// because prost would apply the attribute to another field as well.
+pub fn add_json_attributes(
+    config: &mut prost_build::Config,
+    descriptor: &FileDescriptorSet,
+    options: &SerdeOptions,
+) -> Result<(), Error> {
+    let oneofs = flattened_oneofs(descriptor, options)?;
+    for oneof in oneofs {
+        config.field_attribute(oneof, "#[serde(flatten)]");
+    }
+    let enums = index_enums(descriptor, options);
+    for fd in &descriptor.file {
+        let package_name = fd.package();
+        if options.is_skipped_package(package_name) {
+            continue;
+        }
+        let scope = JsonScope {
+            enums: &enums,
+            proto2: !matches!(fd.syntax(), "proto3" | "editions"),
+            options,
+        };
+        let fq_package = if package_name.is_empty() {
+            String::new()
+        } else {
+            format!(".{package_name}")
+        };
+        let module = module_path(package_name, &[]);
+        for msg in &fd.message_type {
+            message_json_attributes(config, &scope, &fq_package, &module, msg);
+        }
+    }
+    Ok(())
+}
+
+struct JsonScope<'a> {
+    // The enums with generated helper modules by fully qualified name, with the Rust module path
+    // of their helper module.
+    enums: &'a HashMap<String, Vec<String>>,
+    // Proto2 scalars are generated as `Option`s, like proto3 `optional` ones.
+    proto2: bool,
+    options: &'a SerdeOptions,
+}
+
+// Collects the enums `add_serde` generates helper modules for.
+fn index_enums(descriptor: &FileDescriptorSet, options: &SerdeOptions) -> HashMap<String, Vec<String>> {
+    fn add_enums(
+        enums: &mut HashMap<String, Vec<String>>,
+        package_name: &str,
+        parents: &[&str],
+        enum_types: &[EnumDescriptorProto],
+        options: &SerdeOptions,
+    ) {
+        for enum_type in enum_types {
+            let mut path = parents.to_vec();
+            path.push(enum_type.name());
+            let fq_name = fq_name(package_name, &path);
+            if !options.is_extern(&fq_name) {
+                let mut module = module_path(package_name, &[]);
+                module.push(ENUM_SERDE_MODULE.to_string());
+                module.push(enum_serde_module(&path));
+                enums.insert(fq_name, module);
+            }
+        }
+    }
+
+    fn add_message_enums(
+        enums: &mut HashMap<String, Vec<String>>,
+        package_name: &str,
+        parents: &[&str],
+        msg: &DescriptorProto,
+        options: &SerdeOptions,
+    ) {
+        let mut path = parents.to_vec();
+        path.push(msg.name());
+        if options.is_extern(&fq_name(package_name, &path)) {
+            return;
+        }
+        add_enums(enums, package_name, &path, &msg.enum_type, options);
+        for nested in &msg.nested_type {
+            add_message_enums(enums, package_name, &path, nested, options);
+        }
+    }
+
+    let mut enums = HashMap::new();
+    for fd in &descriptor.file {
+        let package_name = fd.package();
+        if options.is_skipped_package(package_name) {
+            continue;
+        }
+        add_enums(&mut enums, package_name, &[], &fd.enum_type, options);
+        for msg in &fd.message_type {
+            add_message_enums(&mut enums, package_name, &[], msg, options);
+        }
+    }
+    enums
+}
+
+// Returns the paths to add `#[serde(flatten)]` for, to flatten the oneofs of the messages that get
+// JSON attributes: proto3 JSON writes the set member of a oneof as a field of the message itself.
+//
+// Prost applies the attributes of `.pkg.Msg.oneof` to its members at `.pkg.Msg.oneof.field` as
+// well, where `flatten` is not allowed, so the attribute is added for the suffix `pkg.Msg.oneof`
+// instead. That suffix also matches the path `.other.pkg.Msg.oneof`, which is fine if that is a
+// oneof to flatten as well, as long as it does not get a second attribute. Any other field it
+// matches is reported as an error, as no path would match the oneof alone.
+fn flattened_oneofs(descriptor: &FileDescriptorSet, options: &SerdeOptions) -> Result<Vec<String>, Error> {
+    struct Paths<'a> {
+        proto_file: &'a str,
+        // The oneofs to flatten, and the paths of all other fields, oneofs and oneof members.
+        oneofs: Vec<(String, &'a str)>,
+        others: Vec<(String, &'a str)>,
+    }
+
+    fn add_paths<'a>(paths: &mut Paths<'a>, parent: &str, msg: &DescriptorProto, flatten: bool, options: &SerdeOptions) {
+        let fq_name = format!("{parent}.{}", msg.name());
+        let flatten = flatten && !options.is_extern(&fq_name);
+        for (index, oneof) in msg.oneof_decl.iter().enumerate() {
+            let members = msg.field.iter().filter(|field| field.oneof_index == Some(index as i32));
+            let path = format!("{fq_name}.{}", oneof.name());
+            for field in members.clone() {
+                paths.others.push((format!("{path}.{}", field.name()), paths.proto_file));
+            }
+            // Synthetic oneofs of proto3 `optional` fields are generated as `Option`s instead.
+            if flatten && !members.clone().all(|field| field.proto3_optional()) {
+                paths.oneofs.push((path, paths.proto_file));
+            } else {
+                paths.others.push((path, paths.proto_file));
+            }
+        }
+        for field in msg.field.iter().filter(|field| field.oneof_index.is_none() || field.proto3_optional()) {
+            paths.others.push((format!("{fq_name}.{}", field.name()), paths.proto_file));
+        }
+        for nested in &msg.nested_type {
+            add_paths(paths, &fq_name, nested, flatten, options);
+        }
+    }
+
+    let mut paths = Paths {
+        proto_file: "",
+        oneofs: Vec::new(),
+        others: Vec::new(),
+    };
+    for fd in &descriptor.file {
+        paths.proto_file = fd.name();
+        let flatten = !options.is_skipped_package(fd.package());
+        let fq_package = if fd.package().is_empty() {
+            String::new()
+        } else {
+            format!(".{}", fd.package())
+        };
+        for msg in &fd.message_type {
+            add_paths(&mut paths, &fq_package, msg, flatten, options);
+        }
+    }
+
+    // Whether prost applies the attributes of the path suffix to `path`.
+    let matches = |path: &str, suffix: &str| {
+        path.strip_suffix(suffix)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+    };
+    // Shorter paths first, so their suffix covers the longer paths ending in it.
+    paths.oneofs.sort_by_key(|(path, _)| path.len());
+    let mut suffixes: Vec<String> = Vec::new();
+    for (oneof, proto_file) in &paths.oneofs {
+        if suffixes.iter().any(|suffix| matches(oneof, suffix)) {
+            continue;
+        }
+        let suffix = &oneof[1..];
+        if let Some((other, other_proto_file)) = paths.others.iter().find(|(other, _)| matches(other, suffix)) {
+            return Err(Error::OneofCollision {
+                oneof: suffix.to_string(),
+                proto_file: proto_file.to_string(),
+                other_path: other[1..].to_string(),
+                other_proto_file: other_proto_file.to_string(),
+            });
+        }
+        suffixes.push(suffix.to_string());
+    }
+    Ok(suffixes)
+}
+
+fn fq_name(package_name: &str, path: &[&str]) -> String {
+    if package_name.is_empty() {
+        format!(".{}", path.join("."))
+    } else {
+        format!(".{package_name}.{}", path.join("."))
+    }
+}
+
+// The Rust module path prost generates the types nested in `parents` of a package into.
+fn module_path(package_name: &str, parents: &[&str]) -> Vec<String> {
+    Module::from_protobuf_package_name(package_name)
+        .parts()
+        .map(str::to_string)
+        .chain(parents.iter().map(|parent| to_field_ident(parent).to_string()))
+        .collect()
+}
+
+// The path of module `to`, relative to module `from`, like prost resolves the paths of types in
+// other packages.
+fn relative_path(from: &[String], to: &[String]) -> String {
+    let common = from.iter().zip(to).take_while(|(from, to)| from == to).count();
+    (common..from.len())
+        .map(|_| "super")
+        .chain(to[common..].iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+// The name of the helper module of the enum with the given path in its package, e.g. `outer__kind`
+// for `Outer.Kind`.
+fn enum_serde_module(path: &[&str]) -> String {
+    match path {
+        [name] => to_field_ident(name).to_string(),
+        _ => path
+            .iter()
+            .map(|name| name.to_snake_case())
+            .collect::<Vec<_>>()
+            .join("__"),
+    }
+}
+
+// Generates the serde helper module of an enum, see `prost_wkt::proto_enum_serde!`, to be placed
+// in the `prost_wkt_enum_serde` module of the generated file of its package.
+pub(crate) fn gen_enum_serde(
+    package_name: &str,
+    parents: &[&str],
+    enum_type: &EnumDescriptorProto,
+    options: &SerdeOptions,
+) -> Option<TokenStream> {
+    let mut path = parents.to_vec();
+    path.push(enum_type.name());
+    let fq_name = fq_name(package_name, &path);
+    if options.is_extern(&fq_name) {
+        return None;
+    }
+
+    let module = format_ident!("{}", enum_serde_module(&path));
+    let modules = parents.iter().map(|parent| to_field_ident(parent));
+    let enum_name = to_type_ident(enum_type.name());
+    let name = &fq_name[1..];
+    Some(quote! {
+        pub mod #module {
+            ::prost_wkt::proto_enum_serde!(super::super:: #(#modules::)* #enum_name, #name);
+        }
+    })
+}
+
+fn message_json_attributes(
+    config: &mut prost_build::Config,
+    scope: &JsonScope,
+    parent: &str,
+    module: &[String],
+    msg: &DescriptorProto,
+) {
+    let fq_name = format!("{parent}.{}", msg.name());
+    if scope.options.is_extern(&fq_name) || msg.options.as_ref().is_some_and(|opts| opts.map_entry()) {
+        return;
+    }
+
+    for field in &msg.field {
+        let json_name = match field.json_name {
+            Some(ref json_name) => json_name.clone(),
+            None => to_json_name(field.name()),
+        };
+        let mut attributes = vec![format!("rename = \"{json_name}\"")];
+        if json_name != field.name() {
+            attributes.push(format!("alias = \"{}\"", field.name()));
+        }
+
+        let path = match field.oneof_index {
+            // Oneof members are variants of the oneof enum, which are never skipped and always
+            // hold a plain value.
+            Some(index) if !field.proto3_optional() => {
+                if is_json_scalar(field.r#type()) {
+                    attributes.push("with = \"::prost_wkt::json::scalar\"".to_string());
+                } else if let Some(enum_module) = scope.enums.get(field.type_name()) {
+                    let mut variant_module = module.to_vec();
+                    variant_module.push(to_field_ident(msg.name()).to_string());
+                    attributes.push(format!("with = \"{}\"", relative_path(&variant_module, enum_module)));
+                }
+                format!("{fq_name}.{}.{}", msg.oneof_decl[index as usize].name(), field.name())
+            }
+            _ => {
+                // Proto2 required fields are always set, so they are written even if default.
+                if field.label() != Label::Required {
+                    attributes.push("skip_serializing_if = \"::prost_wkt::json::is_default\"".to_string());
+                }
+                if let Some(helper) = json_helper(field, msg, scope, module) {
+                    attributes.push(format!("with = \"{helper}\""));
+                }
+                format!("{fq_name}.{}", field.name())
+            }
+        };
+        config.field_attribute(path, format!("#[serde({})]", attributes.join(", ")));
+    }
+
+    let mut nested_module = module.to_vec();
+    nested_module.push(to_field_ident(msg.name()).to_string());
+    for nested in &msg.nested_type {
+        message_json_attributes(config, scope, &fq_name, &nested_module, nested);
+    }
+}
+
+fn is_json_scalar(r#type: Type) -> bool {
+    matches!(
+        r#type,
+        Type::Int64
+            | Type::Sint64
+            | Type::Sfixed64
+            | Type::Uint64
+            | Type::Fixed64
+            | Type::Double
+            | Type::Float
+            | Type::Bytes
+    )
+}
+
+// Returns the module to serialize a field with, if its type needs one: one of the modules in
+// `prost_wkt::json` for 64 bit integers, floats and bytes, or the generated helper module of an enum.
+fn json_helper(field: &FieldDescriptorProto, msg: &DescriptorProto, scope: &JsonScope, module: &[String]) -> Option<String> {
+    let helper = |value: &FieldDescriptorProto, label: &str| {
+        if is_json_scalar(value.r#type()) {
+            Some(format!("::prost_wkt::json::{label}"))
+        } else if value.r#type() == Type::Enum {
+            let enum_module = relative_path(module, scope.enums.get(value.type_name())?);
+            match label {
+                "scalar" => Some(enum_module),
+                _ => Some(format!("{enum_module}::{label}")),
+            }
+        } else {
+            None
+        }
+    };
+
+    if field.label() == Label::Repeated {
+        if field.r#type() == Type::Message {
+            let entry_name = field.type_name().rsplit('.').next()?;
+            let entry = msg
+                .nested_type
+                .iter()
+                .find(|nested| nested.name() == entry_name && nested.options.as_ref().is_some_and(|opts| opts.map_entry()))?;
+            let value = entry.field.iter().find(|field| field.number() == 2)?;
+            return helper(value, "map");
+        }
+        helper(field, "repeated")
+    } else if field.proto3_optional() || (scope.proto2 && field.label() == Label::Optional) {
+        helper(field, "optional")
+    } else {
+        helper(field, "scalar")
+    }
+}
+
+// The default `json_name` protoc derives from a field name, used if the descriptor lacks it.
+fn to_json_name(name: &str) -> String {
+    let mut json_name = String::with_capacity(name.len());
+    let mut upper = false;
+    for c in name.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            json_name.push(c.to_ascii_uppercase());
+            upper = false;
+        } else {
+            json_name.push(c);
+        }
+    }
+    json_name
+}
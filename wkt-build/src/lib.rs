@@ -1,55 +1,344 @@
-use heck::{ToShoutySnakeCase, ToUpperCamelCase};
+use heck::ToShoutySnakeCase;
+use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
-use std::fs::{File, OpenOptions};
+use std::fs::OpenOptions;
 use std::io::Write;
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
 pub use prost::Message;
 pub use prost_types::FileDescriptorSet;
 
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{DescriptorProto, FieldDescriptorProto};
+
 use prost_build::Module;
 
-pub fn add_serde(out: PathBuf, descriptor: FileDescriptorSet) {
-    for fd in &descriptor.file {
-        let package_name = match fd.package {
-            Some(ref pkg) => pkg,
-            None => continue,
+mod config;
+pub use crate::config::*;
+
+mod error;
+pub use crate::error::*;
+
+mod ident;
+use crate::ident::{to_field_ident, to_type_ident};
+
+mod json;
+pub use crate::json::*;
+
+mod validate;
+
+/// Options for `add_serde_with_options`.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct SerdeOptions {
+    /// Prefix of the type URLs the messages are registered with, `type.googleapis.com` by default.
+    pub type_url_prefix: String,
+    /// Proto paths of types generated outside of this build, see `extern_path`.
+    pub extern_paths: Vec<(String, String)>,
+    /// Packages for which no implementations are generated, see `skip_package`.
+    pub skipped_packages: Vec<String>,
+    /// Whether the well known types are generated by this build, see `compile_well_known_types`.
+    pub compile_well_known_types: bool,
+    /// Whether `FieldMaskable` implementations are generated, see `field_masks`.
+    pub field_masks: bool,
+}
+
+impl Default for SerdeOptions {
+    fn default() -> Self {
+        SerdeOptions {
+            type_url_prefix: "type.googleapis.com".to_string(),
+            extern_paths: Vec::new(),
+            skipped_packages: Vec::new(),
+            compile_well_known_types: false,
+            field_masks: false,
+        }
+    }
+}
+
+impl SerdeOptions {
+    /// Sets the prefix of the type URLs, e.g. `type.example.com` results in type URLs like
+    /// `type.example.com/my.package.MyMessage`. An empty prefix results in `/my.package.MyMessage`.
+    pub fn type_url_prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.type_url_prefix = prefix.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Marks the types under `proto_path` as generated elsewhere, mirroring
+    /// `prost_build::Config::extern_path`. Pass the same paths as to prost: no implementations are
+    /// generated for these types, and fields of these types are treated as leaves in field masks.
+    pub fn extern_path<P1, P2>(mut self, proto_path: P1, rust_path: P2) -> Self
+    where
+        P1: Into<String>,
+        P2: Into<String>,
+    {
+        self.extern_paths.push((proto_path.into(), rust_path.into()));
+        self
+    }
+
+    /// Skips all messages of the given protobuf package, e.g. `my.package`, and of the packages
+    /// nested in it, e.g. `my.package.v1`, for packages that are part of the descriptor set but not
+    /// generated by this build.
+    pub fn skip_package<S: Into<String>>(mut self, package: S) -> Self {
+        self.skipped_packages.push(package.into());
+        self
+    }
+
+    /// Generates implementations for the `google.protobuf` types as well, to be used together with
+    /// `prost_build::Config::compile_well_known_types`. Otherwise they are treated as extern types,
+    /// just like prost does.
+    pub fn compile_well_known_types(mut self) -> Self {
+        self.compile_well_known_types = true;
+        self
+    }
+
+    /// Generates `prost_wkt::FieldMaskable` implementations, so the messages can be used with the
+    /// field mask operations of `prost_wkt_types::FieldMask`. Fields are only descended into if
+    /// their message type is generated by this build as well, so every message type prost maps to
+    /// another crate must be passed to `extern_path` too, or the generated code will not compile.
+    pub fn field_masks(mut self) -> Self {
+        self.field_masks = true;
+        self
+    }
+
+    // Returns whether the type with the fully qualified name (e.g. `.my.package.Message`) is
+    // generated outside of this build. Like prost, a path matches the type itself and everything
+    // nested in it.
+    fn is_extern(&self, fq_name: &str) -> bool {
+        let matches = |proto_path: &str| {
+            fq_name == proto_path
+                || (fq_name.starts_with(proto_path) && fq_name[proto_path.len()..].starts_with('.'))
         };
+        (!self.compile_well_known_types && matches(".google.protobuf"))
+            || self.extern_paths.iter().any(|(proto_path, _)| matches(proto_path))
+    }
 
-        let rust_path = out
-            .join(Module::from_protobuf_package_name(package_name).to_file_name_or(package_name));
+    // Returns whether the package or type with the fully qualified name (e.g. `.my.package` or
+    // `.my.package.Message`) belongs to one of the skipped packages, or a package nested in one.
+    fn is_skipped(&self, fq_name: &str) -> bool {
+        self.skipped_packages.iter().any(|pkg| {
+            let pkg = format!(".{}", pkg.trim_start_matches('.'));
+            fq_name == pkg || (fq_name.starts_with(&pkg) && fq_name[pkg.len()..].starts_with('.'))
+        })
+    }
 
-        // In some cases the generated file would be in empty. These files are no longer created by Prost, so
-        // we'll create here. Otherwise we append.
-        let mut rust_file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(rust_path)
-            .unwrap();
+    // Returns whether the files of the package, e.g. `my.package`, are skipped, see `is_skipped`.
+    fn is_skipped_package(&self, package_name: &str) -> bool {
+        !package_name.is_empty() && self.is_skipped(&format!(".{package_name}"))
+    }
+}
+
+/// Generates the `prost-wkt` implementations for the messages in `descriptor`, see
+/// `add_serde_with_options`.
+///
+/// `FieldMaskable` implementations are not generated by default, as they only compile once every
+/// extern path of the prost build is known. Use `add_serde_with_options` with
+/// `SerdeOptions::field_masks` to generate them, or `prost_wkt_build::Config`, which enables them.
+///
+/// # Panics
+///
+/// Panics if the descriptor set fails validation or the code cannot be written, see
+/// `try_add_serde` for a variant that returns the error instead.
+pub fn add_serde(out: PathBuf, descriptor: FileDescriptorSet) {
+    add_serde_with_options(out, descriptor, &SerdeOptions::default())
+}
+
+/// Like `add_serde`, but with the given options. Only messages that prost generated in this build
+/// get implementations, so the options should match the `prost_build::Config` used.
+///
+/// The implementations for a package are written to `{package}.rs.serde` next to the file prost
+/// generated for it, e.g. `my.package.rs.serde` for `my.package.rs`, which is included from the
+/// latter. The file is rewritten on every call, so running `add_serde` again, with or without
+/// prost regenerating its files, gives the same result.
+///
+/// `FieldMaskable` implementations are only generated if `SerdeOptions::field_masks` is set.
+///
+/// # Panics
+///
+/// Panics like `add_serde`, see `try_add_serde_with_options`.
+pub fn add_serde_with_options(out: PathBuf, descriptor: FileDescriptorSet, options: &SerdeOptions) {
+    if let Err(err) = try_add_serde_with_options(out, descriptor, options) {
+        panic!("{err}");
+    }
+}
+
+/// Like `add_serde`, but returns an error instead of panicking.
+pub fn try_add_serde(out: PathBuf, descriptor: FileDescriptorSet) -> Result<(), Error> {
+    try_add_serde_with_options(out, descriptor, &SerdeOptions::default())
+}
 
+/// Like `add_serde_with_options`, but returns an error instead of panicking. Before anything is
+/// written, the descriptor set is checked for conflicts that would otherwise surface as compile
+/// errors in the generated code: messages registered with the same type URL, and types that end up
+/// with the same Rust identifier, e.g. `foo_bar` and `FooBar`.
+pub fn try_add_serde_with_options(
+    out: PathBuf,
+    descriptor: FileDescriptorSet,
+    options: &SerdeOptions,
+) -> Result<(), Error> {
+    validate::validate(&descriptor, options)?;
+
+    // A package may be spread over several proto files, so the code is collected per package
+    // before anything is written.
+    let mut packages: BTreeMap<String, PackageCode> = BTreeMap::new();
+    for fd in &descriptor.file {
+        let package_name = fd.package();
+        if options.is_skipped_package(package_name) {
+            continue;
+        }
+
+        // Prost generates the messages of files without a package into `_.rs`.
+        let file_name = Module::from_protobuf_package_name(package_name).to_file_name_or("_");
+        let code = packages.entry(file_name).or_default();
+        code.proto_files.push(fd.name().to_string());
         for msg in &fd.message_type {
-            let message_name = match msg.name {
-                Some(ref name) => name,
-                None => continue,
+            gen_message_impls(&mut code.impls, &mut code.enums, package_name, &[], msg, options);
+        }
+        for enum_type in &fd.enum_type {
+            code.enums.extend(gen_enum_serde(package_name, &[], enum_type, options));
+        }
+    }
+
+    for (file_name, mut code) in packages {
+        if !code.enums.is_empty() {
+            let enums = &code.enums;
+            // Only referenced by the serde attributes in this crate, and kept out of glob
+            // re-exports, where the modules of several packages would clash.
+            let tokens = quote! {
+                #[allow(dead_code)]
+                pub(crate) mod prost_wkt_enum_serde {
+                    #(#enums)*
+                }
             };
+            writeln!(code.impls).unwrap();
+            writeln!(code.impls, "{}", &tokens).unwrap();
+        }
 
-            let type_url = format!("type.googleapis.com/{package_name}.{message_name}");
+        // Prost only generates `.rs` files, so this cannot overwrite one, e.g. `my.pkg.serde.rs` of
+        // the package `my.pkg.serde`.
+        let serde_name = format!("{file_name}.serde");
+        let include = format!("include!(\"{serde_name}\");\n");
+        let rust_path = out.join(&file_name);
+        let rust_code = std::fs::read_to_string(&rust_path).unwrap_or_default();
+        let included = rust_code.ends_with(&include);
+        if code.impls.is_empty() && !included {
+            continue;
+        }
+        let serde_path = out.join(&serde_name);
+        write_if_changed(&serde_path, &code.impls).map_err(|source| Error::Io {
+            proto_files: code.proto_files.clone(),
+            path: serde_path,
+            source,
+        })?;
 
-            gen_trait_impl(&mut rust_file, package_name, message_name, &type_url);
+        // Prost rewrites its file whenever the generated code differs from it, so the include
+        // is only there already if `add_serde` is run again without prost. Prost does not create
+        // a file at all if e.g. all messages of a package are extern, so it may be created here.
+        if !included {
+            let mut rust_file = OpenOptions::new().create(true).append(true).open(&rust_path);
+            if !rust_code.is_empty() && !rust_code.ends_with('\n') {
+                rust_file = rust_file.and_then(|mut file| writeln!(file).map(|_| file));
+            }
+            rust_file
+                .and_then(|mut file| file.write_all(include.as_bytes()))
+                .map_err(|source| Error::Io {
+                    proto_files: code.proto_files,
+                    path: rust_path,
+                    source,
+                })?;
         }
     }
+    Ok(())
 }
 
-// This method uses the `heck` crate (the same that prost uses) to properly format the message name
-// to UpperCamelCase as the prost_build::ident::{to_snake, to_upper_camel} methods
-// in the `ident` module of prost_build is private.
-fn gen_trait_impl(rust_file: &mut File, package_name: &str, message_name: &str, type_url: &str) {
-    let type_name = message_name.to_upper_camel_case();
-    let type_name = format_ident!("{}", type_name);
+// The code generated for a package, and the proto files it was generated from.
+#[derive(Default)]
+struct PackageCode {
+    proto_files: Vec<String>,
+    impls: Vec<u8>,
+    enums: Vec<TokenStream>,
+}
+
+// Writes `content` to `path`, unless the file already has that content, to not trigger needless
+// rebuilds.
+fn write_if_changed(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    if std::fs::read(path).is_ok_and(|existing| existing == content) {
+        return Ok(());
+    }
+    std::fs::write(path, content)
+}
+
+// Generates the implementations for a message and, recursively, for its nested messages. Nested
+// messages are generated by prost in a module named after their parent, e.g. `Outer.Inner` becomes
+// `outer::Inner`, and are registered with the type URL `{prefix}/{package}.Outer.Inner`, or
+// `{prefix}/Outer.Inner` when the file has no package. The serde helper modules of the enums in
+// the messages are collected into `enums`.
+fn gen_message_impls(
+    rust_file: &mut impl Write,
+    enums: &mut Vec<TokenStream>,
+    package_name: &str,
+    parents: &[&str],
+    msg: &DescriptorProto,
+    options: &SerdeOptions,
+) {
+    // Map entries are generated as `HashMap`s rather than as messages.
+    if msg.options.as_ref().is_some_and(|opts| opts.map_entry()) {
+        return;
+    }
+    let message_name = match msg.name {
+        Some(ref name) => name,
+        None => return,
+    };
+
+    let mut path = parents.to_vec();
+    path.push(message_name);
+    let full_name = path.join(".");
+    let fq_name = if package_name.is_empty() {
+        format!(".{full_name}")
+    } else {
+        format!(".{package_name}.{full_name}")
+    };
+    if options.is_extern(&fq_name) {
+        return;
+    }
+    let type_url = if package_name.is_empty() {
+        format!("{}/{full_name}", options.type_url_prefix)
+    } else {
+        format!("{}/{package_name}.{full_name}", options.type_url_prefix)
+    };
+    let modules: Vec<_> = parents.iter().map(|parent| to_field_ident(parent)).collect();
+
+    gen_trait_impl(rust_file, package_name, &modules, &path, &type_url);
+    if options.field_masks {
+        gen_field_mask_impl(rust_file, &modules, msg, options);
+    }
+
+    for nested in &msg.nested_type {
+        gen_message_impls(rust_file, enums, package_name, &path, nested, options);
+    }
+    for enum_type in &msg.enum_type {
+        enums.extend(gen_enum_serde(package_name, &path, enum_type, options));
+    }
+}
+
+// The type is named like prost names it, see the `ident` module.
+fn gen_trait_impl(
+    rust_file: &mut impl Write,
+    package_name: &str,
+    modules: &[proc_macro2::Ident],
+    path: &[&str],
+    type_url: &str,
+) {
+    let message_name = path.join(".");
+    let type_name = to_type_ident(path[path.len() - 1]);
+    let type_name = quote! { #(#modules::)* #type_name };
 
     let dummy_const = format_ident!(
         "IMPL_MESSAGE_SERDE_FOR_{}",
-        message_name.to_shouty_snake_case()
+        path.iter()
+            .map(|name| name.to_shouty_snake_case())
+            .collect::<Vec<_>>()
+            .join("__")
     );
 
     let tokens = quote! {
@@ -96,3 +385,596 @@ fn gen_trait_impl(rust_file: &mut File, package_name: &str, message_name: &str,
     writeln!(rust_file).unwrap();
     writeln!(rust_file, "{}", &tokens).unwrap();
 }
+
+// How a field is copied or cleared when applying a field mask.
+#[derive(Clone, Copy, PartialEq)]
+enum FieldKind {
+    Scalar,
+    Repeated,
+    // Singular message field. Paths descending into it are only delegated to the nested message
+    // when `nested` is set; well known types, extern types and the types of skipped packages are
+    // treated as leaves as they may come from `prost-types` or another crate without a
+    // `FieldMaskable` implementation.
+    Message { nested: bool },
+}
+
+fn field_kind(field: &FieldDescriptorProto, options: &SerdeOptions) -> FieldKind {
+    if field.label() == Label::Repeated {
+        FieldKind::Repeated
+    } else if matches!(field.r#type(), Type::Message | Type::Group) {
+        FieldKind::Message {
+            nested: !field.type_name().starts_with(".google.protobuf.")
+                && !options.is_extern(field.type_name())
+                && !options.is_skipped(field.type_name()),
+        }
+    } else {
+        FieldKind::Scalar
+    }
+}
+
+// Generates the `FieldMaskable` implementation for a message. Plain fields are handled by the
+// helpers in `prost_wkt`, oneof members are matched on the variant of the generated oneof enum and
+// are always replaced as a whole when merging.
+fn gen_field_mask_impl(
+    rust_file: &mut impl Write,
+    modules: &[proc_macro2::Ident],
+    msg: &DescriptorProto,
+    options: &SerdeOptions,
+) {
+    let message_name = msg.name();
+    let type_name = to_type_ident(message_name);
+    let type_name = quote! { #(#modules::)* #type_name };
+    let oneof_module = to_field_ident(message_name);
+    let oneof_module = quote! { #(#modules::)* #oneof_module };
+
+    let mut field_names = Vec::new();
+    let mut valid_arms = Vec::new();
+    let mut merge_arms = Vec::new();
+    let mut merge_nested_arms = Vec::new();
+    let mut trims = Vec::new();
+    let mut oneof_trims: Vec<Vec<TokenStream>> = vec![Vec::new(); msg.oneof_decl.len()];
+    let mut uses_options = false;
+
+    for field in &msg.field {
+        let field_name = field.name();
+        let kind = field_kind(field, options);
+        field_names.push(field_name);
+
+        let oneof = match field.oneof_index {
+            Some(index) if !field.proto3_optional() => Some(index as usize),
+            _ => None,
+        };
+
+        if let Some(index) = oneof {
+            let oneof_name = msg.oneof_decl[index].name();
+            let oneof_field = to_field_ident(oneof_name);
+            let oneof_type = to_type_ident(oneof_name);
+            let variant = to_type_ident(field_name);
+            let variant = quote! { #oneof_module::#oneof_type::#variant };
+
+            merge_arms.push(quote! {
+                #field_name => {
+                    if matches!(src.#oneof_field, Some(#variant(_))) {
+                        self.#oneof_field = src.#oneof_field.clone();
+                    } else if matches!(self.#oneof_field, Some(#variant(_))) {
+                        self.#oneof_field = None;
+                    }
+                }
+            });
+
+            let trim = match kind {
+                FieldKind::Message { nested: true } => {
+                    uses_options = true;
+                    valid_arms.push(quote! {
+                        #field_name => ::prost_wkt::is_valid_nested_path(
+                            |msg: &Self| match msg.#oneof_field {
+                                Some(#variant(ref value)) => Some(value),
+                                _ => None,
+                            },
+                            rest,
+                        ),
+                    });
+                    merge_nested_arms.push(quote! {
+                        #field_name => {
+                            if let Some(#variant(source)) = &src.#oneof_field {
+                                if !matches!(self.#oneof_field, Some(#variant(_))) {
+                                    self.#oneof_field = Some(#variant(Default::default()));
+                                }
+                                if let Some(#variant(target)) = &mut self.#oneof_field {
+                                    ::prost_wkt::FieldMaskable::merge_path(target, source, rest, options);
+                                }
+                            } else if let Some(#variant(target)) = &mut self.#oneof_field {
+                                ::prost_wkt::FieldMaskable::merge_path(target, &Default::default(), rest, options);
+                            }
+                        }
+                    });
+                    quote! {
+                        Some(#variant(value)) => match ::prost_wkt::field_paths(paths, #field_name) {
+                            ::prost_wkt::FieldPaths::All => true,
+                            ::prost_wkt::FieldPaths::None => false,
+                            ::prost_wkt::FieldPaths::Nested(nested) => {
+                                ::prost_wkt::FieldMaskable::trim_paths(value, &nested);
+                                true
+                            }
+                        },
+                    }
+                }
+                FieldKind::Message { nested: false } => quote! {
+                    Some(#variant(_)) => !matches!(
+                        ::prost_wkt::field_paths(paths, #field_name),
+                        ::prost_wkt::FieldPaths::None
+                    ),
+                },
+                _ => quote! {
+                    Some(#variant(_)) => matches!(
+                        ::prost_wkt::field_paths(paths, #field_name),
+                        ::prost_wkt::FieldPaths::All
+                    ),
+                },
+            };
+            oneof_trims[index].push(trim);
+            continue;
+        }
+
+        let field_ident = to_field_ident(field_name);
+        uses_options |= kind != FieldKind::Scalar;
+        match kind {
+            FieldKind::Scalar => {
+                merge_arms.push(quote! {
+                    #field_name => self.#field_ident = src.#field_ident.clone(),
+                });
+                trims.push(quote! {
+                    if !matches!(::prost_wkt::field_paths(paths, #field_name), ::prost_wkt::FieldPaths::All) {
+                        self.#field_ident = Default::default();
+                    }
+                });
+            }
+            FieldKind::Repeated => {
+                merge_arms.push(quote! {
+                    #field_name => ::prost_wkt::merge_repeated(&mut self.#field_ident, &src.#field_ident, options),
+                });
+                trims.push(quote! {
+                    if !matches!(::prost_wkt::field_paths(paths, #field_name), ::prost_wkt::FieldPaths::All) {
+                        self.#field_ident = Default::default();
+                    }
+                });
+            }
+            FieldKind::Message { nested } => {
+                merge_arms.push(quote! {
+                    #field_name => ::prost_wkt::merge_message(&mut self.#field_ident, &src.#field_ident, options),
+                });
+                if nested {
+                    valid_arms.push(quote! {
+                        #field_name => ::prost_wkt::is_valid_nested_path(|msg: &Self| msg.#field_ident.as_ref(), rest),
+                    });
+                    merge_nested_arms.push(quote! {
+                        #field_name => ::prost_wkt::merge_nested(&mut self.#field_ident, &src.#field_ident, rest, options),
+                    });
+                    trims.push(quote! {
+                        match ::prost_wkt::field_paths(paths, #field_name) {
+                            ::prost_wkt::FieldPaths::All => {}
+                            ::prost_wkt::FieldPaths::None => self.#field_ident = Default::default(),
+                            ::prost_wkt::FieldPaths::Nested(nested) => {
+                                ::prost_wkt::trim_nested(&mut self.#field_ident, &nested)
+                            }
+                        }
+                    });
+                } else {
+                    trims.push(quote! {
+                        if matches!(::prost_wkt::field_paths(paths, #field_name), ::prost_wkt::FieldPaths::None) {
+                            self.#field_ident = Default::default();
+                        }
+                    });
+                }
+            }
+        }
+    }
+
+    for (oneof, arms) in msg.oneof_decl.iter().zip(oneof_trims) {
+        if arms.is_empty() {
+            continue;
+        }
+        let oneof_field = to_field_ident(oneof.name());
+        trims.push(quote! {
+            let keep = match &mut self.#oneof_field {
+                #(#arms)*
+                None => true,
+            };
+            if !keep {
+                self.#oneof_field = None;
+            }
+        });
+    }
+
+    let valid_leaf = if field_names.is_empty() {
+        quote! { None => false, }
+    } else {
+        quote! { None => matches!(path, #(#field_names)|*), }
+    };
+    let valid_nested = if valid_arms.is_empty() {
+        quote! { Some(_) => false, }
+    } else {
+        quote! {
+            Some((field, rest)) => match field {
+                #(#valid_arms)*
+                _ => false,
+            },
+        }
+    };
+
+    let merge_body = if field_names.is_empty() {
+        quote! {}
+    } else {
+        let merge_nested = if merge_nested_arms.is_empty() {
+            quote! { Some(_) => {} }
+        } else {
+            quote! {
+                Some((field, rest)) => match field {
+                    #(#merge_nested_arms)*
+                    _ => {}
+                },
+            }
+        };
+        quote! {
+            match path.split_once('.') {
+                None => match path {
+                    #(#merge_arms)*
+                    _ => {}
+                },
+                #merge_nested
+            }
+        }
+    };
+    let (src, path, options) = if field_names.is_empty() {
+        (format_ident!("_src"), format_ident!("_path"), format_ident!("_options"))
+    } else if uses_options {
+        (format_ident!("src"), format_ident!("path"), format_ident!("options"))
+    } else {
+        (format_ident!("src"), format_ident!("path"), format_ident!("_options"))
+    };
+    let paths = if trims.is_empty() {
+        format_ident!("_paths")
+    } else {
+        format_ident!("paths")
+    };
+
+    let tokens = quote! {
+        #[allow(clippy::all, deprecated)]
+        impl ::prost_wkt::FieldMaskable for #type_name {
+            fn is_valid_path(path: &str) -> bool {
+                match path.split_once('.') {
+                    #valid_leaf
+                    #valid_nested
+                }
+            }
+
+            fn merge_path(&mut self, #src: &Self, #path: &str, #options: &::prost_wkt::MergeOptions) {
+                #merge_body
+            }
+
+            fn trim_paths(&mut self, #paths: &[&str]) {
+                #(#trims)*
+            }
+        }
+    };
+
+    writeln!(rust_file).unwrap();
+    writeln!(rust_file, "{}", &tokens).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost_types::{FileDescriptorProto, OneofDescriptorProto};
+
+    // Creates an empty directory for the files generated by one test.
+    fn out_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("prost-wkt-build-{}-{test}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn read(path: PathBuf) -> String {
+        std::fs::read_to_string(&path).unwrap_or_else(|err| panic!("{}: {err}", path.display()))
+    }
+
+    fn file(name: &str, package: &str, messages: Vec<DescriptorProto>) -> FileDescriptorProto {
+        FileDescriptorProto {
+            name: Some(name.to_string()),
+            package: Some(package.to_string()).filter(|package| !package.is_empty()),
+            message_type: messages,
+            syntax: Some("proto3".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn message(name: &str, fields: Vec<FieldDescriptorProto>) -> DescriptorProto {
+        DescriptorProto {
+            name: Some(name.to_string()),
+            field: fields,
+            ..Default::default()
+        }
+    }
+
+    fn field(name: &str, number: i32, r#type: Type, type_name: Option<&str>) -> FieldDescriptorProto {
+        FieldDescriptorProto {
+            name: Some(name.to_string()),
+            number: Some(number),
+            label: Some(Label::Optional as i32),
+            r#type: Some(r#type as i32),
+            type_name: type_name.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn field_masks_treat_extern_messages_as_leaves() {
+        let out = out_dir("extern_field");
+        let descriptor = FileDescriptorSet {
+            file: vec![
+                file("other.proto", "other", vec![message("Ext", vec![])]),
+                file(
+                    "my.proto",
+                    "my.pkg",
+                    vec![message(
+                        "Foo",
+                        vec![
+                            field("ext", 1, Type::Message, Some(".other.Ext")),
+                            field("inner", 2, Type::Message, Some(".my.pkg.Foo")),
+                        ],
+                    )],
+                ),
+            ],
+        };
+
+        let options = SerdeOptions::default().extern_path(".other", "::other");
+        try_add_serde_with_options(out.clone(), descriptor.clone(), &options).unwrap();
+        let code = read(out.join("my.pkg.rs.serde"));
+        assert!(code.contains("impl :: prost_wkt :: MessageSerde for Foo"));
+        assert!(!code.contains("FieldMaskable"));
+        assert!(!out.join("other.rs.serde").exists());
+
+        try_add_serde_with_options(out.clone(), descriptor, &options.field_masks()).unwrap();
+        let code = read(out.join("my.pkg.rs.serde"));
+        assert!(code.contains("impl :: prost_wkt :: FieldMaskable for Foo"));
+        assert!(code.contains("\"inner\" => :: prost_wkt :: is_valid_nested_path"));
+        assert!(!code.contains("\"ext\" => :: prost_wkt :: is_valid_nested_path"));
+        assert!(code.contains("\"ext\" => :: prost_wkt :: merge_message"));
+    }
+
+    #[test]
+    fn package_less_protos_are_written_to_underscore_file() {
+        let out = out_dir("package_less");
+        std::fs::write(out.join("_.rs"), "pub struct Plain {}\n").unwrap();
+        let descriptor = FileDescriptorSet {
+            file: vec![file("plain.proto", "", vec![message("Plain", vec![])])],
+        };
+
+        try_add_serde(out.clone(), descriptor).unwrap();
+        let code = read(out.join("_.rs.serde"));
+        assert!(code.contains("impl :: prost_wkt :: MessageSerde for Plain"));
+        assert!(code.contains("\"type.googleapis.com/Plain\""));
+        assert_eq!(read(out.join("_.rs")), "pub struct Plain {}\ninclude!(\"_.rs.serde\");\n");
+    }
+
+    #[test]
+    fn extern_paths_and_skipped_packages_are_not_generated() {
+        let out = out_dir("extern_skipped");
+        let descriptor = FileDescriptorSet {
+            file: vec![
+                file("other.proto", "other", vec![message("Other", vec![])]),
+                file("skipped.proto", "skipped", vec![message("Skipped", vec![])]),
+                file("nested.proto", "skipped.nested", vec![message("Nested", vec![])]),
+                file(
+                    "my.proto",
+                    "my.pkg",
+                    vec![
+                        message(
+                            "Foo",
+                            vec![
+                                field("nested", 1, Type::Message, Some(".skipped.nested.Nested")),
+                                field("inner", 2, Type::Message, Some(".my.pkg.Foo")),
+                            ],
+                        ),
+                        message("Ext", vec![]),
+                    ],
+                ),
+            ],
+        };
+
+        let options = SerdeOptions::default()
+            .extern_path(".other", "::other")
+            .extern_path(".my.pkg.Ext", "::other::Ext")
+            .skip_package("skipped")
+            .field_masks();
+        // Prost generates all packages, but only those not skipped get JSON attributes.
+        let prost_out = out_dir("extern_skipped_prost");
+        let mut config = prost_build::Config::new();
+        add_json_attributes(&mut config, &descriptor, &options).unwrap();
+        config.out_dir(&prost_out).compile_fds(descriptor.clone()).unwrap();
+        assert!(read(prost_out.join("my.pkg.rs")).contains("#[serde("));
+        assert!(!read(prost_out.join("skipped.nested.rs")).contains("#[serde("));
+
+        try_add_serde_with_options(out.clone(), descriptor, &options).unwrap();
+        let code = read(out.join("my.pkg.rs.serde"));
+        assert!(code.contains("impl :: prost_wkt :: MessageSerde for Foo"));
+        assert!(!code.contains("for Ext"));
+        assert!(code.contains("\"inner\" => :: prost_wkt :: is_valid_nested_path"));
+        assert!(!code.contains("\"nested\" => :: prost_wkt :: is_valid_nested_path"));
+        for name in ["other", "skipped", "skipped.nested"] {
+            assert!(!out.join(format!("{name}.rs.serde")).exists(), "{name}");
+            assert!(!out.join(format!("{name}.rs")).exists(), "{name}");
+        }
+    }
+
+    #[test]
+    fn rerunning_leaves_files_unchanged() {
+        let out = out_dir("rerun");
+        let prost_code = "pub struct Foo {}\n";
+        std::fs::write(out.join("my.pkg.rs"), prost_code).unwrap();
+        let descriptor = FileDescriptorSet {
+            file: vec![file("my.proto", "my.pkg", vec![message("Foo", vec![])])],
+        };
+        let included = format!("{prost_code}include!(\"my.pkg.rs.serde\");\n");
+
+        try_add_serde(out.clone(), descriptor.clone()).unwrap();
+        let serde_path = out.join("my.pkg.rs.serde");
+        let code = read(serde_path.clone());
+        assert_eq!(read(out.join("my.pkg.rs")), included);
+
+        // Running again without prost neither rewrites the serde file nor adds another include.
+        let mtime = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        std::fs::File::options()
+            .write(true)
+            .open(&serde_path)
+            .and_then(|file| file.set_modified(mtime))
+            .unwrap();
+        try_add_serde(out.clone(), descriptor.clone()).unwrap();
+        assert_eq!(std::fs::metadata(&serde_path).unwrap().modified().unwrap(), mtime);
+        assert_eq!(read(serde_path.clone()), code);
+        assert_eq!(read(out.join("my.pkg.rs")), included);
+
+        // Once prost rewrites its file, the include is added again.
+        std::fs::write(out.join("my.pkg.rs"), prost_code).unwrap();
+        try_add_serde(out.clone(), descriptor).unwrap();
+        assert_eq!(read(out.join("my.pkg.rs")), included);
+        assert_eq!(read(serde_path), code);
+    }
+
+    #[test]
+    fn conflicts_are_reported_before_writing() {
+        let out = out_dir("conflicts");
+        let descriptor = FileDescriptorSet {
+            file: vec![
+                file("a.proto", "my.pkg", vec![message("Foo", vec![])]),
+                file("b.proto", "my.pkg", vec![message("Foo", vec![])]),
+            ],
+        };
+        match try_add_serde(out.clone(), descriptor) {
+            Err(Error::DuplicateTypeUrl {
+                type_url,
+                proto_file,
+                other_proto_file,
+            }) => {
+                assert_eq!(type_url, "type.googleapis.com/my.pkg.Foo");
+                assert_eq!(proto_file, "b.proto");
+                assert_eq!(other_proto_file, "a.proto");
+            }
+            other => panic!("expected DuplicateTypeUrl, got {other:?}"),
+        }
+
+        let descriptor = FileDescriptorSet {
+            file: vec![
+                file("a.proto", "my.pkg", vec![message("foo_bar", vec![])]),
+                file("b.proto", "my.pkg", vec![message("FooBar", vec![])]),
+            ],
+        };
+        let err = try_add_serde(out.clone(), descriptor).unwrap_err();
+        assert_eq!(err.proto_file(), Some("b.proto"));
+        match err {
+            Error::IdentCollision {
+                rust_path,
+                proto_name,
+                other_proto_name,
+                other_proto_file,
+                ..
+            } => {
+                assert_eq!(rust_path, "my::pkg::FooBar");
+                assert_eq!(proto_name, "my.pkg.FooBar");
+                assert_eq!(other_proto_name, "my.pkg.foo_bar");
+                assert_eq!(other_proto_file, "a.proto");
+            }
+            other => panic!("expected IdentCollision, got {other:?}"),
+        }
+
+        assert_eq!(std::fs::read_dir(&out).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn serde_files_do_not_clash_with_prost_files() {
+        let out = out_dir("clash");
+        // Prost generates `acme.serde.rs` for the package `acme.serde`.
+        let descriptor = FileDescriptorSet {
+            file: vec![
+                file("acme.proto", "acme", vec![message("Foo", vec![])]),
+                file("serde.proto", "acme.serde", vec![message("Bar", vec![])]),
+            ],
+        };
+
+        prost_build::Config::new()
+            .out_dir(&out)
+            .compile_fds(descriptor.clone())
+            .unwrap();
+        try_add_serde(out.clone(), descriptor).unwrap();
+        let prost_code = read(out.join("acme.serde.rs"));
+        assert!(prost_code.contains("pub struct Bar"));
+        assert!(prost_code.ends_with("include!(\"acme.serde.rs.serde\");\n"));
+        assert!(read(out.join("acme.rs.serde")).contains("impl :: prost_wkt :: MessageSerde for Foo"));
+        assert!(read(out.join("acme.serde.rs.serde")).contains("impl :: prost_wkt :: MessageSerde for Bar"));
+        assert!(read(out.join("acme.rs")).ends_with("include!(\"acme.rs.serde\");\n"));
+    }
+
+    fn oneof_message(name: &str, oneof: &str) -> DescriptorProto {
+        let mut field = field("value", 1, Type::String, None);
+        field.oneof_index = Some(0);
+        DescriptorProto {
+            oneof_decl: vec![OneofDescriptorProto {
+                name: Some(oneof.to_string()),
+                ..Default::default()
+            }],
+            ..message(name, vec![field])
+        }
+    }
+
+    #[test]
+    fn oneofs_are_flattened_once() {
+        let out = out_dir("oneofs");
+        // The attribute for `pkg.Msg.choice` matches `.other.pkg.Msg.choice` as well.
+        let descriptor = FileDescriptorSet {
+            file: vec![
+                file("a.proto", "pkg", vec![oneof_message("Msg", "choice")]),
+                file("b.proto", "other.pkg", vec![oneof_message("Msg", "choice")]),
+            ],
+        };
+
+        let mut config = prost_build::Config::new();
+        add_json_attributes(&mut config, &descriptor, &SerdeOptions::default()).unwrap();
+        config.out_dir(&out).compile_fds(descriptor).unwrap();
+        for file_name in ["pkg.rs", "other.pkg.rs"] {
+            let code = read(out.join(file_name));
+            assert_eq!(code.matches("#[serde(flatten)]").count(), 1, "{code}");
+            assert!(code.contains("#[serde(flatten)]\n    pub choice"), "{code}");
+        }
+    }
+
+    #[test]
+    fn oneofs_colliding_with_fields_are_reported() {
+        let descriptor = FileDescriptorSet {
+            file: vec![
+                file("a.proto", "pkg", vec![oneof_message("Msg", "choice")]),
+                file(
+                    "b.proto",
+                    "other.pkg",
+                    vec![message("Msg", vec![field("choice", 1, Type::String, None)])],
+                ),
+            ],
+        };
+
+        let mut config = prost_build::Config::new();
+        match add_json_attributes(&mut config, &descriptor, &SerdeOptions::default()) {
+            Err(Error::OneofCollision {
+                oneof,
+                proto_file,
+                other_path,
+                other_proto_file,
+            }) => {
+                assert_eq!(oneof, "pkg.Msg.choice");
+                assert_eq!(proto_file, "a.proto");
+                assert_eq!(other_path, "other.pkg.Msg.choice");
+                assert_eq!(other_proto_file, "b.proto");
+            }
+            other => panic!("expected OneofCollision, got {other:?}"),
+        }
+    }
+}
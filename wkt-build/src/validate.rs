@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+
+use prost_build::Module;
+use prost_types::{DescriptorProto, FileDescriptorSet};
+
+use crate::ident::{to_field_ident, to_upper_camel};
+use crate::{Error, SerdeOptions};
+
+// Checks the types `add_serde` generates code for, so that conflicts are reported with the proto
+// names involved instead of as compile errors in the generated code.
+pub(crate) fn validate(descriptor: &FileDescriptorSet, options: &SerdeOptions) -> Result<(), Error> {
+    let mut validator = Validator {
+        options,
+        proto_file: "",
+        type_urls: HashMap::new(),
+        idents: HashMap::new(),
+    };
+    for fd in &descriptor.file {
+        let package_name = fd.package();
+        if options.is_skipped_package(package_name) {
+            continue;
+        }
+        validator.proto_file = fd.name();
+        let module: Vec<String> = Module::from_protobuf_package_name(package_name)
+            .parts()
+            .map(str::to_string)
+            .collect();
+        let fq_package = if package_name.is_empty() {
+            String::new()
+        } else {
+            format!(".{package_name}")
+        };
+        for enum_type in &fd.enum_type {
+            let fq_name = format!("{fq_package}.{}", enum_type.name());
+            if !options.is_extern(&fq_name) {
+                validator.add_ident(&module, enum_type.name(), fq_name)?;
+            }
+        }
+        for msg in &fd.message_type {
+            validator.add_message(&module, &fq_package, msg)?;
+        }
+    }
+    Ok(())
+}
+
+struct Validator<'a> {
+    options: &'a SerdeOptions,
+    // The proto file currently being validated.
+    proto_file: &'a str,
+    // The proto file of every type URL.
+    type_urls: HashMap<String, &'a str>,
+    // The proto name, without leading dot, and file of every generated type, by Rust path.
+    idents: HashMap<String, (String, &'a str)>,
+}
+
+impl<'a> Validator<'a> {
+    fn add_message(&mut self, module: &[String], parent: &str, msg: &'a DescriptorProto) -> Result<(), Error> {
+        let fq_name = format!("{parent}.{}", msg.name());
+        if self.options.is_extern(&fq_name) || msg.options.as_ref().is_some_and(|opts| opts.map_entry()) {
+            return Ok(());
+        }
+
+        let type_url = format!("{}/{}", self.options.type_url_prefix, &fq_name[1..]);
+        if let Some(other_proto_file) = self.type_urls.insert(type_url.clone(), self.proto_file) {
+            return Err(Error::DuplicateTypeUrl {
+                type_url,
+                proto_file: self.proto_file.to_string(),
+                other_proto_file: other_proto_file.to_string(),
+            });
+        }
+        self.add_ident(module, msg.name(), fq_name.clone())?;
+
+        // Nested types and the enums of oneofs are generated into a module named after the message.
+        let mut nested_module = module.to_vec();
+        nested_module.push(to_field_ident(msg.name()).to_string());
+        for (index, oneof) in msg.oneof_decl.iter().enumerate() {
+            // Synthetic oneofs of proto3 `optional` fields are generated as `Option`s instead.
+            let synthetic = msg
+                .field
+                .iter()
+                .filter(|field| field.oneof_index == Some(index as i32))
+                .all(|field| field.proto3_optional());
+            if !synthetic {
+                self.add_ident(&nested_module, oneof.name(), format!("{fq_name}.{}", oneof.name()))?;
+            }
+        }
+        for enum_type in &msg.enum_type {
+            self.add_ident(&nested_module, enum_type.name(), format!("{fq_name}.{}", enum_type.name()))?;
+        }
+        for nested in &msg.nested_type {
+            self.add_message(&nested_module, &fq_name, nested)?;
+        }
+        Ok(())
+    }
+
+    fn add_ident(&mut self, module: &[String], name: &str, proto_name: String) -> Result<(), Error> {
+        let mut rust_path = module.to_vec();
+        rust_path.push(to_upper_camel(name));
+        let rust_path = rust_path.join("::");
+        match self.idents.get(&rust_path) {
+            Some((other_proto_name, other_proto_file)) => Err(Error::IdentCollision {
+                rust_path,
+                proto_name: proto_name[1..].to_string(),
+                proto_file: self.proto_file.to_string(),
+                other_proto_name: other_proto_name.clone(),
+                other_proto_file: other_proto_file.to_string(),
+            }),
+            None => {
+                self.idents.insert(rust_path, (proto_name[1..].to_string(), self.proto_file));
+                Ok(())
+            }
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use prost::Message;
+use prost_wkt::{MessageSerde, MessageSerdeDecoderEntry};
+use prost_wkt_types::*;
+
+const TYPE_URL: &str = "type.googleapis.com/google.protobuf.Api";
+
+fn decode_empty(buf: &[u8]) -> Result<Box<dyn MessageSerde>, prost::DecodeError> {
+    Ok(Box::new(Empty::decode(buf)?))
+}
+
+// Registers 32 * 32 additional messages, so the lookups run against a registry of the size a
+// larger code base generates.
+macro_rules! filler_entries {
+    ($($a:ident)*) => {
+        filler_entries!(@outer [$($a)*] $($a)*);
+    };
+    (@outer $names:tt $($a:ident)*) => {
+        $(filler_entries!(@inner $a $names);)*
+    };
+    (@inner $a:ident [$($b:ident)*]) => {
+        $(
+            prost_wkt::inventory::submit! {
+                MessageSerdeDecoderEntry {
+                    type_url: concat!("type.googleapis.com/bench.", stringify!($a), stringify!($b)),
+                    decoder: decode_empty,
+                }
+            }
+        )*
+    };
+}
+
+filler_entries!(
+    a b c d e f g h i j k l m n o p q r s t u v w x y z aa bb cc dd ee ff
+);
+
+fn create_any() -> Any {
+    let api = Api {
+        name: "pkg.Service".to_string(),
+        methods: vec![Method {
+            name: "Get".to_string(),
+            request_type_url: "type.googleapis.com/pkg.GetRequest".to_string(),
+            response_type_url: "type.googleapis.com/pkg.Foo".to_string(),
+            ..Default::default()
+        }],
+        version: "v1".to_string(),
+        ..Default::default()
+    };
+    Any::try_pack(api).unwrap()
+}
+
+fn bench_lookup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lookup");
+    for (name, type_url) in [("registered", TYPE_URL), ("missing", "type.googleapis.com/bench.Missing")] {
+        group.bench_function(format!("linear/{name}"), |b| {
+            b.iter(|| {
+                prost_wkt::inventory::iter::<MessageSerdeDecoderEntry>
+                    .into_iter()
+                    .find(|entry| entry.type_url == black_box(type_url))
+            })
+        });
+        group.bench_function(format!("indexed/{name}"), |b| {
+            b.iter(|| prost_wkt::find_decoder(black_box(type_url)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_any(c: &mut Criterion) {
+    let any = create_any();
+    let json = serde_json::to_string(&any).unwrap();
+
+    c.bench_function("try_unpack", |b| {
+        b.iter(|| black_box(any.clone()).try_unpack().unwrap())
+    });
+    c.bench_function("serialize", |b| {
+        b.iter(|| serde_json::to_string(black_box(&any)).unwrap())
+    });
+    c.bench_function("deserialize", |b| {
+        b.iter(|| serde_json::from_str::<Any>(black_box(&json)).unwrap())
+    });
+}
+
+criterion_group!(benches, bench_lookup, bench_any);
+criterion_main!(benches);
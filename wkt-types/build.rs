@@ -17,48 +17,110 @@ fn main() {
     let dir = PathBuf::from(env::var("OUT_DIR").unwrap());
     process_prost_pbtime(&dir);
 
-    build(&dir, "pbtime");
-    build(&dir, "pbstruct");
-    build(&dir, "pbany");
-    build(&dir, "pbempty");
-    build(&dir, "pbmask");
+    build(&dir, "pbtime", &[]);
+    build(&dir, "pbstruct", &[]);
+    build(&dir, "pbany", &[]);
+    build(&dir, "pbempty", &[]);
+    build(&dir, "pbmask", &[]);
+    build(&dir, "pbwrappers", &[]);
+    build(&dir, "pbtype", &[(".google.protobuf.Any", "crate::Any")]);
+    #[cfg(feature = "descriptor")]
+    build(&dir, "pbdescriptor", &[]);
 }
 
-fn build(dir: &Path, proto: &str) {
+/// Compiles `proto/{proto}.proto` into its own directory in `OUT_DIR`. Types listed in
+/// `extern_paths` are already generated by another module, so neither prost nor `add_serde`
+/// generate them again.
+fn build(dir: &Path, proto: &str, extern_paths: &[(&str, &str)]) {
     let out = dir.join(proto);
     create_dir_all(&out).unwrap();
     let source = format!("proto/{proto}.proto");
-    let descriptor_file = out.join("descriptors.bin");
+    let descriptor = compile_descriptor_set(&source, &out.join("descriptors.bin"));
     let mut prost_build = prost_build::Config::new();
-    
-    #[cfg(feature = "vendored-protox")]
-    {
-        let file_descriptors = protox::compile(&[source.clone()], &["proto/".to_string()]).unwrap();
-        std::fs::write(&descriptor_file, file_descriptors.encode_to_vec()).unwrap();
-        prost_build.skip_protoc_run();
+
+    for (proto_path, rust_path) in extern_paths {
+        prost_build.extern_path(*proto_path, *rust_path);
     }
-    
+
+    json_attributes(&mut prost_build, &descriptor, extern_paths);
+
     prost_build
         .compile_well_known_types()
-        .type_attribute("google.protobuf.Duration","#[derive(serde_derive::Serialize, serde_derive::Deserialize)] #[serde(default)]")
         .type_attribute("google.protobuf.Empty","#[derive(serde_derive::Serialize, serde_derive::Deserialize)]")
-        .type_attribute("google.protobuf.FieldMask","#[derive(serde_derive::Serialize, serde_derive::Deserialize)]")
         .type_attribute("google.protobuf.Timestamp", "#[diesel(sql_type = diesel::sql_types::Timestamptz)]")
         .type_attribute("google.protobuf.Timestamp", "#[derive(diesel::expression::AsExpression, diesel::deserialize::FromSqlRow)]")
-        .file_descriptor_set_path(&descriptor_file)
         .out_dir(&out)
-        .compile_protos(
-            &[
-                source
-            ],
-            &["proto/".to_string()],
-        )
+        .compile_fds(descriptor.clone())
         .unwrap();
 
+    let options = extern_paths.iter().fold(
+        prost_wkt_build::SerdeOptions::default().compile_well_known_types().field_masks(),
+        |options, (proto_path, rust_path)| options.extern_path(*proto_path, *rust_path),
+    );
+    prost_wkt_build::add_serde_with_options(out, descriptor, &options);
+}
+
+/// Compiles `source` and its imports into a `FileDescriptorSet`. The set is needed before code
+/// generation, as some serde attributes depend on the field types.
+fn compile_descriptor_set(source: &str, descriptor_file: &Path) -> FileDescriptorSet {
+    #[cfg(feature = "vendored-protox")]
+    {
+        let file_descriptors = protox::compile(&[source], &["proto/"]).unwrap();
+        std::fs::write(descriptor_file, file_descriptors.encode_to_vec()).unwrap();
+    }
+
+    #[cfg(not(feature = "vendored-protox"))]
+    {
+        let mut cmd = std::process::Command::new(prost_build::protoc_from_env());
+        cmd.arg("--include_imports")
+            .arg("--include_source_info")
+            .arg("-o")
+            .arg(descriptor_file)
+            .arg("-I")
+            .arg("proto/");
+        if let Some(protoc_include) = prost_build::protoc_include_from_env() {
+            cmd.arg("-I").arg(protoc_include);
+        }
+        let output = cmd.arg(source).output().expect("failed to run protoc");
+        if !output.status.success() {
+            panic!("protoc failed: {}", String::from_utf8_lossy(&output.stderr));
+        }
+    }
+
     let descriptor_bytes = std::fs::read(descriptor_file).unwrap();
-    let descriptor = FileDescriptorSet::decode(&descriptor_bytes[..]).unwrap();
+    FileDescriptorSet::decode(&descriptor_bytes[..]).unwrap()
+}
 
-    prost_wkt_build::add_serde(out, descriptor);
+/// The well known types whose serde implementations are derived, following the proto3 JSON
+/// mapping. The other well known types have serde implementations of their own.
+const DERIVED_SERDE_PROTOS: &[&str] = &[
+    "google/protobuf/api.proto",
+    "google/protobuf/descriptor.proto",
+    "google/protobuf/source_context.proto",
+    "google/protobuf/type.proto",
+];
+
+/// Derives serde for the messages of the `DERIVED_SERDE_PROTOS` in `descriptor` and adds the field
+/// attributes of `add_json_attributes` for them.
+fn json_attributes(prost_build: &mut prost_build::Config, descriptor: &FileDescriptorSet, extern_paths: &[(&str, &str)]) {
+    let files: Vec<_> = descriptor
+        .file
+        .iter()
+        .filter(|fd| DERIVED_SERDE_PROTOS.contains(&fd.name()))
+        .cloned()
+        .collect();
+    for message in files.iter().flat_map(|fd| &fd.message_type) {
+        // Message attributes match by prefix, so this covers the nested messages as well.
+        prost_build.message_attribute(
+            format!(".google.protobuf.{}", message.name()),
+            "#[derive(serde_derive::Serialize, serde_derive::Deserialize)] #[serde(default)]",
+        );
+    }
+    let options = extern_paths.iter().fold(
+        prost_wkt_build::SerdeOptions::default().compile_well_known_types(),
+        |options, (proto_path, rust_path)| options.extern_path(*proto_path, *rust_path),
+    );
+    prost_wkt_build::add_json_attributes(prost_build, &FileDescriptorSet { file: files }, &options).unwrap();
 }
 
 fn process_prost_pbtime(dir: &Path) {
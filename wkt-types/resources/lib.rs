@@ -123,7 +123,7 @@ impl fmt::Display for Duration {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut d = self.clone();
         d.normalize();
-        if self.seconds < 0 && self.nanos < 0 {
+        if d.seconds < 0 || d.nanos < 0 {
             write!(f, "-")?;
         }
         write!(f, "{}", d.seconds.abs())?;
@@ -15,4 +15,20 @@ pub use crate::pbempty::*;
 mod pbmask;
 pub use crate::pbmask::*;
 
-pub use prost_wkt::MessageSerde;
+mod pbwrappers;
+pub use crate::pbwrappers::*;
+
+// Not glob exported, as the `Option` message would shadow `std::option::Option` for anyone
+// importing `prost_wkt_types::*`.
+mod pbtype;
+pub use crate::pbtype::{
+    field, Api, Enum, EnumValue, Field, Method, Mixin, Option as ProtoOption, SourceContext, Syntax,
+    Type,
+};
+
+#[cfg(feature = "descriptor")]
+mod pbdescriptor;
+#[cfg(feature = "descriptor")]
+pub use crate::pbdescriptor::*;
+
+pub use prost_wkt::{FieldMaskable, MergeOptions, MessageSerde, TypeRegistry};
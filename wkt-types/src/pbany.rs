@@ -1,16 +1,33 @@
-use prost_wkt::MessageSerde;
-use serde::de::{Deserialize, Deserializer};
-use serde::ser::{Serialize, SerializeStruct, Serializer};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use prost_wkt::{MessageSerde, TypeRegistry};
+use serde::de::{self, Deserialize, DeserializeSeed, Deserializer};
+use serde::ser::{self, Serialize, SerializeStruct, Serializer};
 
 include!(concat!(env!("OUT_DIR"), "/pbany/google.protobuf.rs"));
 
-use prost::{DecodeError, Message};
+use prost::{DecodeError, EncodeError, Message};
 
 use std::borrow::Cow;
 
+/// Error returned when packing or unpacking an `Any`.
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct AnyError {
-    description: Cow<'static, str>,
+#[non_exhaustive]
+pub enum AnyError {
+    /// No message type is registered for the type URL.
+    UnknownType { type_url: String },
+    /// The value of the `Any` could not be decoded as the message type of the type URL.
+    Decode {
+        type_url: String,
+        source: DecodeError,
+    },
+    /// The message could not be encoded into the value of the `Any`.
+    Encode {
+        type_url: String,
+        source: EncodeError,
+    },
+    /// Any other error, as created by `AnyError::new`.
+    Other(Cow<'static, str>),
 }
 
 impl AnyError {
@@ -18,34 +35,64 @@ impl AnyError {
     where
         S: Into<Cow<'static, str>>,
     {
-        AnyError {
-            description: description.into(),
-        }
+        AnyError::Other(description.into())
+    }
+
+    /// Returns the type URL of the message the error is about, if known.
+    pub fn type_url(&self) -> Option<&str> {
+        let type_url = match self {
+            AnyError::UnknownType { type_url }
+            | AnyError::Decode { type_url, .. }
+            | AnyError::Encode { type_url, .. } => type_url,
+            AnyError::Other(_) => return None,
+        };
+        Some(type_url.as_str()).filter(|url| !url.is_empty())
     }
 }
 
 impl std::error::Error for AnyError {
-    fn description(&self) -> &str {
-        &self.description
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AnyError::Decode { source, .. } => Some(source),
+            AnyError::Encode { source, .. } => Some(source),
+            _ => None,
+        }
     }
 }
 
 impl std::fmt::Display for AnyError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str("failed to convert Value: ")?;
-        f.write_str(&self.description)
+        match self {
+            AnyError::UnknownType { type_url } => write!(
+                f,
+                "failed to unpack Any: {type_url} is not registered, make sure prost-wkt-build is executed"
+            ),
+            AnyError::Decode { type_url, source } => write!(f, "failed to decode {type_url}: {source}"),
+            AnyError::Encode { type_url, source } => write!(f, "failed to encode {type_url}: {source}"),
+            AnyError::Other(description) => write!(f, "failed to convert Any: {description}"),
+        }
     }
 }
 
-impl From<prost::DecodeError> for AnyError {
+/// Converts a decode error of a message whose type URL is not known. Prefer constructing
+/// `AnyError::Decode` with the type URL.
+impl From<DecodeError> for AnyError {
     fn from(error: DecodeError) -> Self {
-        AnyError::new(format!("Error decoding message: {error:?}"))
+        AnyError::Decode {
+            type_url: String::new(),
+            source: error,
+        }
     }
 }
 
-impl From<prost::EncodeError> for AnyError {
-    fn from(error: prost::EncodeError) -> Self {
-        AnyError::new(format!("Error encoding message: {error:?}"))
+/// Converts an encode error of a message whose type URL is not known. Prefer constructing
+/// `AnyError::Encode` with the type URL.
+impl From<EncodeError> for AnyError {
+    fn from(error: EncodeError) -> Self {
+        AnyError::Encode {
+            type_url: String::new(),
+            source: error,
+        }
     }
 }
 
@@ -69,6 +116,20 @@ impl Any {
         }
     }
 
+    /// Packs a message into an `Any` like `try_pack`, but with a `type_url` using the given
+    /// prefix instead of the one the message was generated with, e.g.
+    /// `type.example.com/package_name.struct_name` for the prefix `type.example.com`.
+    pub fn try_pack_with_prefix<T>(message: T, prefix: &str) -> Result<Self, AnyError>
+    where
+        T: Message + MessageSerde + Default,
+    {
+        let type_name = prost_wkt::type_name_from_url(MessageSerde::type_url(&message));
+        let type_url = format!("{}/{}", prefix.trim_end_matches('/'), type_name);
+        let mut encoded = Self::try_pack(message)?;
+        encoded.type_url = type_url;
+        Ok(encoded)
+    }
+
     /// Packs a message into an `Any` containing a `type_url` which will take the format
     /// of `type.googleapis.com/package_name.struct_name`, and a value containing the
     /// encoded message.
@@ -80,7 +141,10 @@ impl Any {
         // Serialize the message into a value
         let mut buf = Vec::new();
         buf.reserve(message.encoded_len());
-        message.encode(&mut buf)?;
+        message.encode(&mut buf).map_err(|source| AnyError::Encode {
+            type_url: type_url.clone(),
+            source,
+        })?;
         let encoded = Any {
             type_url,
             value: buf,
@@ -94,10 +158,49 @@ impl Any {
     /// let back: Foo = any.unpack_as(Foo::default())?;
     /// ```
     pub fn unpack_as<T: Message>(self, mut target: T) -> Result<T, AnyError> {
-        let instance = target.merge(self.value.as_slice()).map(|_| target)?;
+        let instance = target
+            .merge(self.value.as_slice())
+            .map(|_| target)
+            .map_err(|source| self.decode_error(source))?;
         Ok(instance)
     }
 
+    /// Returns `true` if the `Any` contains a message of type `T`, i.e. if the message name of its
+    /// `type_url` is the one of `T`. The prefix of the `type_url` is ignored.
+    pub fn is<T>(&self) -> bool
+    where
+        T: MessageSerde + Default,
+    {
+        prost_wkt::type_name_from_url(T::default().type_url()) == self.type_name()
+    }
+
+    /// Unpacks the contents of the `Any` into a message of type `T`. Returns `None` if the `Any`
+    /// contains a message of another type. Example usage:
+    ///
+    /// ```ignore
+    /// if let Some(foo) = any.unpack_to::<Foo>()? {
+    ///     println!("{foo:?}");
+    /// }
+    /// ```
+    pub fn unpack_to<T>(self) -> Result<Option<T>, AnyError>
+    where
+        T: MessageSerde + Default,
+    {
+        self.unpack_to_ref()
+    }
+
+    /// Like `unpack_to`, but without consuming the `Any`.
+    pub fn unpack_to_ref<T>(&self) -> Result<Option<T>, AnyError>
+    where
+        T: MessageSerde + Default,
+    {
+        if !self.is::<T>() {
+            return Ok(None);
+        }
+        let message = T::decode(self.value.as_slice()).map_err(|source| self.decode_error(source))?;
+        Ok(Some(message))
+    }
+
     #[deprecated(since = "0.3.0", note = "Method renamed to `try_unpack`")]
     pub fn unpack(self) -> Result<Box<dyn prost_wkt::MessageSerde>, AnyError> {
         self.try_unpack()
@@ -110,53 +213,366 @@ impl Any {
     /// let back: Box<dyn MessageSerde> = any.try_unpack()?;
     /// ```
     pub fn try_unpack(self) -> Result<Box<dyn prost_wkt::MessageSerde>, AnyError> {
-        ::prost_wkt::inventory::iter::<::prost_wkt::MessageSerdeDecoderEntry>
-            .into_iter()
-            .find(|entry| self.type_url == entry.type_url)
-            .ok_or_else(|| format!("Failed to deserialize {}. Make sure prost-wkt-build is executed.", self.type_url))
-            .and_then(|entry| {
-                (entry.decoder)(&self.value).map_err(|error| {
-                    format!(
-                        "Failed to deserialize {}. Make sure it implements prost::Message. Error reported: {}",
-                        self.type_url,
-                        error
-                    )
-                })
-            })
-            .map_err(AnyError::new)
+        self.try_unpack_ref()
+    }
+
+    /// Like `try_unpack`, but without consuming the `Any`.
+    pub fn try_unpack_ref(&self) -> Result<Box<dyn prost_wkt::MessageSerde>, AnyError> {
+        let entry = prost_wkt::find_decoder(&self.type_url).ok_or_else(|| self.unknown_type_error())?;
+        (entry.decoder)(&self.value).map_err(|source| self.decode_error(source))
+    }
+
+    /// Unpacks the contents of the `Any` into the `MessageSerde` trait object, using the message
+    /// types of `registry` instead of the ones registered at compile time. Example usage:
+    ///
+    /// ```ignore
+    /// let mut registry = TypeRegistry::new();
+    /// registry.register::<Foo>();
+    /// let back: Box<dyn MessageSerde> = any.try_unpack_with(&registry)?;
+    /// ```
+    pub fn try_unpack_with(self, registry: &TypeRegistry) -> Result<Box<dyn prost_wkt::MessageSerde>, AnyError> {
+        registry
+            .decode(&self.type_url, &self.value)
+            .ok_or_else(|| self.unknown_type_error())?
+            .map_err(|source| self.decode_error(source))
+    }
+
+    fn unknown_type_error(&self) -> AnyError {
+        AnyError::UnknownType {
+            type_url: self.type_url.clone(),
+        }
+    }
+
+    fn decode_error(&self, source: DecodeError) -> AnyError {
+        AnyError::Decode {
+            type_url: self.type_url.clone(),
+            source,
+        }
+    }
+
+    /// Returns the fully qualified name of the packed message, i.e. the part of the `type_url`
+    /// after the last `/`.
+    pub fn type_name(&self) -> &str {
+        prost_wkt::type_name_from_url(&self.type_url)
     }
 }
 
+/// Generates the (de)serialization of the well known types that have a special JSON
+/// representation. Inside an `Any` that JSON is stored in a `value` field next to `@type`, e.g.
+/// `{"@type": "type.googleapis.com/google.protobuf.Duration", "value": "1.5s"}`.
+macro_rules! special_json_types {
+    ($($name:literal => $ty:ty),* $(,)?) => {
+        fn has_special_json(type_name: &str) -> bool {
+            matches!(type_name, $($name)|*)
+        }
+
+        fn serialize_special_json<S>(state: &mut S, type_name: &str, value: &[u8]) -> Result<(), S::Error>
+        where
+            S: SerializeStruct,
+        {
+            match type_name {
+                $($name => {
+                    let message = <$ty>::decode(value).map_err(ser::Error::custom)?;
+                    state.serialize_field("value", &message)
+                })*
+                _ => Err(ser::Error::custom(format!("{type_name} has no special JSON representation"))),
+            }
+        }
+
+        fn deserialize_special_json(type_name: &str, value: serde_json::Value) -> Result<Vec<u8>, serde_json::Error> {
+            match type_name {
+                $($name => serde_json::from_value::<$ty>(value).map(|message| message.encode_to_vec()),)*
+                _ => Err(de::Error::custom(format!("{type_name} has no special JSON representation"))),
+            }
+        }
+    };
+}
+
+special_json_types! {
+    "google.protobuf.Any" => Any,
+    "google.protobuf.Timestamp" => crate::Timestamp,
+    "google.protobuf.Duration" => crate::Duration,
+    "google.protobuf.FieldMask" => crate::FieldMask,
+    "google.protobuf.Struct" => crate::Struct,
+    "google.protobuf.Value" => crate::Value,
+    "google.protobuf.ListValue" => crate::ListValue,
+    "google.protobuf.DoubleValue" => crate::DoubleValue,
+    "google.protobuf.FloatValue" => crate::FloatValue,
+    "google.protobuf.Int64Value" => crate::Int64Value,
+    "google.protobuf.UInt64Value" => crate::UInt64Value,
+    "google.protobuf.Int32Value" => crate::Int32Value,
+    "google.protobuf.UInt32Value" => crate::UInt32Value,
+    "google.protobuf.BoolValue" => crate::BoolValue,
+    "google.protobuf.StringValue" => crate::StringValue,
+    "google.protobuf.BytesValue" => crate::BytesValue,
+}
+
 impl Serialize for Any {
     fn serialize<S>(&self, serializer: S) -> Result<<S as Serializer>::Ok, <S as Serializer>::Error>
     where
         S: Serializer,
     {
-        match self.clone().try_unpack() {
-            Ok(result) => serde::ser::Serialize::serialize(result.as_ref(), serializer),
-            Err(_) => {
-                let mut state = serializer.serialize_struct("Any", 3)?;
-                state.serialize_field("@type", &self.type_url)?;
-                state.serialize_field("value", &self.value)?;
-                state.end()
+        let type_name = self.type_name();
+        if has_special_json(type_name) {
+            let mut state = serializer.serialize_struct("Any", 2)?;
+            state.serialize_field("@type", &self.type_url)?;
+            serialize_special_json(&mut state, type_name, &self.value)?;
+            return state.end();
+        }
+        let decoded = prost_wkt::find_decoder(&self.type_url)
+            .and_then(|entry| (entry.decoder)(&self.value).ok());
+        match decoded {
+            None => self.serialize_unknown(serializer, None),
+            Some(result) if result.type_url() == self.type_url => {
+                serde::ser::Serialize::serialize(result.as_ref(), serializer)
+            }
+            Some(result) => {
+                // Registered under a different prefix, so keep the type URL of this `Any`.
+                let mut json = serde_json::to_value(result.as_ref()).map_err(ser::Error::custom)?;
+                json["@type"] = serde_json::Value::String(self.type_url.clone());
+                json.serialize(serializer)
             }
         }
     }
 }
 
+/// Stands in for `prost_reflect::DescriptorPool` without the `reflect` feature, so the code
+/// paths taking an optional pool do not need to be duplicated.
+#[cfg(not(feature = "reflect"))]
+enum DescriptorPool {}
+
+#[cfg(feature = "reflect")]
+use prost_reflect::DescriptorPool;
+
+impl Any {
+    /// Serializes the `Any` like its `Serialize` implementation, but resolves message types that
+    /// are not compiled into the binary with `pool` instead of the global descriptor pool.
+    #[cfg(feature = "reflect")]
+    pub fn serialize_with_descriptor_pool<S>(&self, serializer: S, pool: &DescriptorPool) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if has_special_json(self.type_name()) || prost_wkt::find_decoder(&self.type_url).is_some() {
+            return self.serialize(serializer);
+        }
+        self.serialize_unknown(serializer, Some(pool))
+    }
+
+    /// Serializes a message type that is not compiled into the binary. With the `reflect`
+    /// feature the message is written field by field if `pool`, or the global descriptor pool,
+    /// describes it. Otherwise the encoded message is written as base64, to be read back as is.
+    fn serialize_unknown<S>(&self, serializer: S, pool: Option<&DescriptorPool>) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        #[cfg(feature = "reflect")]
+        {
+            let descriptor = match pool {
+                Some(pool) => pool.get_message_by_name(self.type_name()),
+                None => DescriptorPool::global().get_message_by_name(self.type_name()),
+            };
+            if let Some(descriptor) = descriptor {
+                let message = prost_reflect::DynamicMessage::decode(descriptor, self.value.as_slice())
+                    .map_err(ser::Error::custom)?;
+                let mut json = serde_json::to_value(&message).map_err(ser::Error::custom)?;
+                if let Some(fields) = json.as_object_mut() {
+                    fields.insert("@type".to_string(), serde_json::Value::String(self.type_url.clone()));
+                }
+                return json.serialize(serializer);
+            }
+        }
+        #[cfg(not(feature = "reflect"))]
+        let _ = pool;
+
+        let mut state = serializer.serialize_struct("Any", 2)?;
+        state.serialize_field("@type", &self.type_url)?;
+        state.serialize_field("value", &STANDARD.encode(&self.value))?;
+        state.end()
+    }
+}
+
 impl<'de> Deserialize<'de> for Any {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
-        let erased: Box<dyn prost_wkt::MessageSerde> =
-            serde::de::Deserialize::deserialize(deserializer)?;
-        let type_url = erased.type_url().to_string();
-        let value = erased.try_encoded().map_err(|err| {
-            serde::de::Error::custom(format!("Failed to encode message: {err:?}"))
-        })?;
-        Ok(Any { type_url, value })
+        // The `@type` field decides how the remaining fields are read, so the JSON is buffered
+        // before handing it to the message type.
+        let json = serde_json::Value::deserialize(deserializer)?;
+        any_from_json(json, None, None)
+    }
+}
+
+/// Deserializes an `Any` using the message types of a `TypeRegistry` instead of the ones
+/// registered at compile time. Example usage:
+///
+/// ```ignore
+/// let mut deserializer = serde_json::Deserializer::from_str(json);
+/// let any: Any = AnySeed::new(&registry).deserialize(&mut deserializer)?;
+/// ```
+///
+/// With the `reflect` feature, message types that are in neither can be described by a
+/// `prost_reflect::DescriptorPool`, see `AnySeed::from_descriptor_pool`.
+#[derive(Clone, Copy)]
+pub struct AnySeed<'a> {
+    registry: Option<&'a TypeRegistry>,
+    pool: Option<&'a DescriptorPool>,
+}
+
+impl<'a> AnySeed<'a> {
+    pub fn new(registry: &'a TypeRegistry) -> Self {
+        AnySeed {
+            registry: Some(registry),
+            pool: None,
+        }
+    }
+
+    /// Deserializes message types that are not registered at compile time as dynamic messages
+    /// described by `pool`, instead of the global descriptor pool.
+    #[cfg(feature = "reflect")]
+    pub fn from_descriptor_pool(pool: &'a DescriptorPool) -> Self {
+        AnySeed {
+            registry: None,
+            pool: Some(pool),
+        }
+    }
+
+    /// Sets the descriptor pool describing the message types missing from the registry.
+    #[cfg(feature = "reflect")]
+    pub fn descriptor_pool(mut self, pool: &'a DescriptorPool) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+}
+
+impl<'a> std::fmt::Debug for AnySeed<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AnySeed")
+            .field("registry", &self.registry)
+            .field("pool", &self.pool.is_some())
+            .finish()
+    }
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for AnySeed<'a> {
+    type Value = Any;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let json = serde_json::Value::deserialize(deserializer)?;
+        any_from_json(json, self.registry, self.pool)
+    }
+}
+
+fn any_from_json<E: de::Error>(
+    mut json: serde_json::Value,
+    registry: Option<&TypeRegistry>,
+    pool: Option<&DescriptorPool>,
+) -> Result<Any, E> {
+    let type_url = json
+        .get("@type")
+        .and_then(serde_json::Value::as_str)
+        .ok_or_else(|| de::Error::missing_field("@type"))?
+        .to_string();
+    let type_name = prost_wkt::type_name_from_url(&type_url);
+    if has_special_json(type_name) {
+        let value = json
+            .get_mut("value")
+            .map(serde_json::Value::take)
+            .ok_or_else(|| de::Error::missing_field("value"))?;
+        let value = deserialize_special_json(type_name, value).map_err(de::Error::custom)?;
+        return Ok(Any { type_url, value });
+    }
+
+    let known = match registry {
+        Some(registry) => registry.contains(&type_url),
+        None => prost_wkt::find_decoder(&type_url).is_some(),
+    };
+    if !known {
+        if let Some(value) = dynamic_from_json(&type_url, &mut json, pool) {
+            return Ok(Any {
+                type_url,
+                value: value.map_err(de::Error::custom)?,
+            });
+        }
+        // Written by the `Serialize` fallback for unknown messages, see above. Any other key means
+        // the object holds the fields of the message, which cannot be encoded without its type.
+        let fallback = json
+            .as_object()
+            .is_some_and(|fields| fields.len() == 2 && fields.contains_key("value"));
+        return match json.get_mut("value").map(serde_json::Value::take) {
+            Some(value @ serde_json::Value::String(_)) if fallback => {
+                let value = crate::BytesValue::deserialize(value).map_err(de::Error::custom)?;
+                Ok(Any {
+                    type_url,
+                    value: value.into(),
+                })
+            }
+            _ => Err(de::Error::custom(format!(
+                "Failed to deserialize {type_url}. The message type is unknown and there is no base64 value"
+            ))),
+        };
+    }
+
+    let erased: Box<dyn MessageSerde> = match registry {
+        Some(registry) => {
+            let tag = json.as_object_mut().and_then(|fields| fields.remove("@type"));
+            match registry.from_json(&type_url, &json) {
+                Some(result) => result.map_err(de::Error::custom)?,
+                None => {
+                    // Registered with only a decoder, so fall back to its `MessageSerde` impl.
+                    json["@type"] = tag.unwrap_or_default();
+                    erased_from_json(json)?
+                }
+            }
+        }
+        None => erased_from_json(json)?,
+    };
+    let value = erased.try_encoded().map_err(|err| {
+        serde::de::Error::custom(format!("Failed to encode message: {err:?}"))
+    })?;
+    Ok(Any { type_url, value })
+}
+
+/// Deserializes and encodes a message type that is not compiled into the binary, if `pool`, or
+/// the global descriptor pool, describes it.
+#[cfg(feature = "reflect")]
+fn dynamic_from_json(
+    type_url: &str,
+    json: &mut serde_json::Value,
+    pool: Option<&DescriptorPool>,
+) -> Option<Result<Vec<u8>, serde_json::Error>> {
+    let type_name = prost_wkt::type_name_from_url(type_url);
+    let descriptor = match pool {
+        Some(pool) => pool.get_message_by_name(type_name),
+        None => DescriptorPool::global().get_message_by_name(type_name),
+    }?;
+    if let Some(fields) = json.as_object_mut() {
+        fields.remove("@type");
     }
+    Some(prost_reflect::DynamicMessage::deserialize(descriptor, json.take()).map(|message| message.encode_to_vec()))
+}
+
+#[cfg(not(feature = "reflect"))]
+fn dynamic_from_json(
+    _type_url: &str,
+    _json: &mut serde_json::Value,
+    _pool: Option<&DescriptorPool>,
+) -> Option<Result<Vec<u8>, serde_json::Error>> {
+    None
+}
+
+/// Deserializes a message through its `MessageSerde` implementation.
+fn erased_from_json<E: de::Error>(mut json: serde_json::Value) -> Result<Box<dyn MessageSerde>, E> {
+    // The message is looked up by the type URL it was registered with, which may use a
+    // different prefix.
+    let type_url = json.get("@type").and_then(serde_json::Value::as_str).unwrap_or_default();
+    if let Some(entry) = prost_wkt::find_decoder(type_url) {
+        json["@type"] = serde_json::Value::String(entry.type_url.to_string());
+    }
+    serde_json::from_value(json).map_err(de::Error::custom)
 }
 
 #[cfg(test)]
@@ -238,4 +654,292 @@ mod tests {
         println!("Deserialize default: {foo:?}");
         assert_eq!(foo, &Foo::default())
     }
+
+    #[test]
+    fn serialize_special_json_test() {
+        let duration = crate::Duration {
+            seconds: 1,
+            nanos: 500_000_000,
+        };
+        let any = Any::try_pack(duration).unwrap();
+        let json = serde_json::to_value(&any).unwrap();
+        assert_eq!(
+            json,
+            json!({"@type": "type.googleapis.com/google.protobuf.Duration", "value": "1.500s"})
+        );
+        let back: Any = serde_json::from_value(json).unwrap();
+        assert_eq!(back, any);
+    }
+
+    #[test]
+    fn serialize_special_json_wrappers_test() {
+        let any = Any::try_pack(crate::Int64Value::from(-5)).unwrap();
+        let json = serde_json::to_value(&any).unwrap();
+        assert_eq!(json["value"], "-5");
+        assert_eq!(serde_json::from_value::<Any>(json).unwrap(), any);
+
+        let any = Any::try_pack(crate::Value::from(String::from("Hello"))).unwrap();
+        let json = serde_json::to_value(&any).unwrap();
+        assert_eq!(json["value"], "Hello");
+        assert_eq!(serde_json::from_value::<Any>(json).unwrap(), any);
+    }
+
+    #[test]
+    fn serialize_nested_any_test() {
+        let inner = Any::try_pack(crate::Timestamp {
+            seconds: 1_577_836_800,
+            nanos: 0,
+        })
+        .unwrap();
+        let any = Any::try_pack(inner).unwrap();
+        let json = serde_json::to_value(&any).unwrap();
+        assert_eq!(
+            json,
+            json!({
+                "@type": "type.googleapis.com/google.protobuf.Any",
+                "value": {
+                    "@type": "type.googleapis.com/google.protobuf.Timestamp",
+                    "value": "2020-01-01T00:00:00Z"
+                }
+            })
+        );
+        let back: Any = serde_json::from_value(json).unwrap();
+        assert_eq!(back, any);
+    }
+
+    #[test]
+    fn deserialize_special_json_errors_test() {
+        let missing = json!({"@type": "type.googleapis.com/google.protobuf.Duration"});
+        assert!(serde_json::from_value::<Any>(missing).is_err());
+        let invalid = json!({"@type": "type.googleapis.com/google.protobuf.Duration", "value": "1"});
+        assert!(serde_json::from_value::<Any>(invalid).is_err());
+        let untyped = json!({"value": "1s"});
+        assert!(serde_json::from_value::<Any>(untyped).is_err());
+    }
+
+    #[test]
+    fn unpack_custom_prefix_test() {
+        let api = crate::Api {
+            name: "pkg.Service".to_string(),
+            ..Default::default()
+        };
+        let any = Any::try_pack_with_prefix(api.clone(), "type.example.com/").unwrap();
+        assert_eq!(any.type_url, "type.example.com/google.protobuf.Api");
+        assert_eq!(any.type_name(), "google.protobuf.Api");
+        let unpacked = any.clone().try_unpack().unwrap();
+        assert_eq!(unpacked.downcast_ref::<crate::Api>(), Some(&api));
+
+        let bare = Any {
+            type_url: "/google.protobuf.Api".to_string(),
+            value: any.value.clone(),
+        };
+        assert!(bare.try_unpack().is_ok());
+    }
+
+    #[test]
+    fn serialize_custom_prefix_test() {
+        let api = crate::Api {
+            name: "pkg.Service".to_string(),
+            ..Default::default()
+        };
+        let any = Any::try_pack_with_prefix(api, "type.example.com").unwrap();
+        let json = serde_json::to_value(&any).unwrap();
+        assert_eq!(json["@type"], "type.example.com/google.protobuf.Api");
+        assert_eq!(json["name"], "pkg.Service");
+        let back: Any = serde_json::from_value(json).unwrap();
+        assert_eq!(back, any);
+    }
+
+    #[test]
+    fn unpack_with_registry_test() {
+        let msg = Foo {
+            string: "Hello World!".to_string(),
+        };
+        let any = Any::try_pack(msg.clone()).unwrap();
+        // `Foo` is not submitted to the inventory, so only the registry knows about it.
+        assert!(any.clone().try_unpack().is_err());
+        assert!(any.clone().try_unpack_with(&TypeRegistry::new()).is_err());
+
+        let mut registry = TypeRegistry::new();
+        registry.register::<Foo>();
+        let unpacked = any.try_unpack_with(&registry).unwrap();
+        assert_eq!(unpacked.downcast_ref::<Foo>(), Some(&msg));
+    }
+
+    #[test]
+    fn scoped_registry_test() {
+        let mut parent = TypeRegistry::new();
+        parent.register::<Foo>();
+        let parent = std::sync::Arc::new(parent);
+        let mut registry = TypeRegistry::scoped(parent.clone());
+        registry.register_decoder("type.googleapis.com/google.protobuf.Api", |buf| {
+            Ok(Box::new(crate::Api::decode(buf)?))
+        });
+
+        assert!(registry.contains("type.googleapis.com/any.test.Foo"));
+        assert!(registry.contains("/google.protobuf.Api"));
+        assert!(!parent.contains("type.googleapis.com/google.protobuf.Api"));
+
+        let api = crate::Api {
+            name: "pkg.Service".to_string(),
+            ..Default::default()
+        };
+        let any = Any::try_pack(api.clone()).unwrap();
+        let unpacked = any.try_unpack_with(&registry).unwrap();
+        assert_eq!(unpacked.downcast_ref::<crate::Api>(), Some(&api));
+    }
+
+    #[test]
+    fn deserialize_with_seed_test() {
+        let data = json!({
+            "@type": "type.googleapis.com/any.test.Foo",
+            "string": "Hello World!"
+        });
+        let mut registry = TypeRegistry::new();
+        assert!(AnySeed::new(&registry).deserialize(&data).is_err());
+
+        registry.register::<Foo>();
+        let any = AnySeed::new(&registry).deserialize(&data).unwrap();
+        let foo = any.unpack_as(Foo::default()).unwrap();
+        assert_eq!(foo.string, "Hello World!");
+
+        let duration = json!({
+            "@type": "type.googleapis.com/google.protobuf.Duration",
+            "value": "1s"
+        });
+        assert!(AnySeed::new(&registry).deserialize(&duration).is_ok());
+    }
+
+    #[test]
+    fn unknown_any_round_trip_test() {
+        let any = Any {
+            type_url: "type.googleapis.com/any.test.Unknown".to_string(),
+            value: vec![0x0a, 0x02, 0xfb, 0xff],
+        };
+        let json = serde_json::to_value(&any).unwrap();
+        assert_eq!(
+            json,
+            json!({"@type": "type.googleapis.com/any.test.Unknown", "value": "CgL7/w=="})
+        );
+        let back: Any = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(back, any);
+        let back = AnySeed::new(&TypeRegistry::new()).deserialize(&json).unwrap();
+        assert_eq!(back, any);
+
+        let invalid = json!({"@type": "type.googleapis.com/any.test.Unknown", "value": "!!"});
+        assert!(serde_json::from_value::<Any>(invalid).is_err());
+
+        // The fields of an unknown message, one of which happens to be a string named `value`.
+        let fields = json!({"@type": "type.googleapis.com/any.test.Unknown", "value": "abc", "name": "x"});
+        let err = serde_json::from_value::<Any>(fields.clone()).unwrap_err();
+        assert!(err.to_string().contains("The message type is unknown"));
+        assert!(AnySeed::new(&TypeRegistry::new()).deserialize(&fields).is_err());
+    }
+
+    #[cfg(feature = "reflect")]
+    fn create_descriptor_pool() -> prost_reflect::DescriptorPool {
+        use prost_reflect::prost_types::field_descriptor_proto::{Label, Type};
+        use prost_reflect::prost_types::{DescriptorProto, FieldDescriptorProto, FileDescriptorProto};
+
+        let field = |name: &str, json_name: &str, number: i32, r#type: Type| FieldDescriptorProto {
+            name: Some(name.to_string()),
+            json_name: Some(json_name.to_string()),
+            number: Some(number),
+            label: Some(Label::Optional as i32),
+            r#type: Some(r#type as i32),
+            ..Default::default()
+        };
+        let file = FileDescriptorProto {
+            name: Some("dynamic.proto".to_string()),
+            package: Some("any.dynamic".to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("Bar".to_string()),
+                field: vec![
+                    field("display_name", "displayName", 1, Type::String),
+                    field("count", "count", 2, Type::Int64),
+                ],
+                ..Default::default()
+            }],
+            syntax: Some("proto3".to_string()),
+            ..Default::default()
+        };
+        let mut pool = prost_reflect::DescriptorPool::new();
+        pool.add_file_descriptor_proto(file).unwrap();
+        pool
+    }
+
+    #[cfg(feature = "reflect")]
+    #[test]
+    fn dynamic_message_test() {
+        let pool = create_descriptor_pool();
+        let data = json!({
+            "@type": "type.googleapis.com/any.dynamic.Bar",
+            "displayName": "Hello",
+            "count": "5"
+        });
+        let any = AnySeed::from_descriptor_pool(&pool).deserialize(&data).unwrap();
+        assert_eq!(any.type_url, "type.googleapis.com/any.dynamic.Bar");
+        assert_eq!(any.value, vec![0x0a, 0x05, b'H', b'e', b'l', b'l', b'o', 0x10, 0x05]);
+
+        let json = any
+            .serialize_with_descriptor_pool(serde_json::value::Serializer, &pool)
+            .unwrap();
+        assert_eq!(json, data);
+
+        // Without the pool the message is unknown and written as base64.
+        let json = serde_json::to_value(&any).unwrap();
+        assert_eq!(json["value"], "CgVIZWxsbxAF");
+    }
+
+    #[test]
+    fn unpack_to_test() {
+        let msg = Foo {
+            string: "Hello World!".to_string(),
+        };
+        let any = Any::try_pack(msg.clone()).unwrap();
+        assert!(any.is::<Foo>());
+        assert!(!any.is::<crate::Empty>());
+        assert_eq!(any.unpack_to_ref::<crate::Empty>().unwrap(), None);
+        assert_eq!(any.unpack_to_ref::<Foo>().unwrap(), Some(msg.clone()));
+        assert_eq!(any.clone().unpack_to::<Foo>().unwrap(), Some(msg));
+
+        let prefixed = Any::try_pack_with_prefix(crate::Empty {}, "type.example.com").unwrap();
+        assert!(prefixed.is::<crate::Empty>());
+        assert_eq!(prefixed.unpack_to::<crate::Empty>().unwrap(), Some(crate::Empty {}));
+
+        let invalid = Any {
+            type_url: "type.googleapis.com/any.test.Foo".to_string(),
+            value: vec![0x0a, 0x05],
+        };
+        assert!(invalid.unpack_to::<Foo>().is_err());
+    }
+
+    #[test]
+    fn structured_error_test() {
+        use std::error::Error;
+
+        let unknown = Any {
+            type_url: "type.googleapis.com/any.test.Missing".to_string(),
+            value: vec![],
+        };
+        let err = unknown.try_unpack().unwrap_err();
+        assert!(matches!(err, AnyError::UnknownType { .. }));
+        assert_eq!(err.type_url(), Some("type.googleapis.com/any.test.Missing"));
+        assert!(err.source().is_none());
+
+        let invalid = Any {
+            type_url: "type.googleapis.com/any.test.Foo".to_string(),
+            value: vec![0x0a, 0x05],
+        };
+        let err = invalid.clone().unpack_as(Foo::default()).unwrap_err();
+        assert!(matches!(err, AnyError::Decode { .. }));
+        assert_eq!(err.type_url(), Some("type.googleapis.com/any.test.Foo"));
+        assert!(err.source().unwrap().is::<DecodeError>());
+        assert!(err.to_string().starts_with("failed to decode type.googleapis.com/any.test.Foo: "));
+
+        let err = AnyError::new("custom");
+        assert_eq!(err, AnyError::Other("custom".into()));
+        assert_eq!(err.type_url(), None);
+        assert_eq!(err.to_string(), "failed to convert Any: custom");
+    }
 }
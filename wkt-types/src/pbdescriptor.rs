@@ -0,0 +1,115 @@
+include!(concat!(env!("OUT_DIR"), "/pbdescriptor/google.protobuf.rs"));
+
+#[cfg(test)]
+mod tests {
+
+    use crate::pbdescriptor::*;
+    use crate::Any;
+
+    fn create_file_descriptor_set() -> FileDescriptorSet {
+        FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some("pkg/foo.proto".to_string()),
+                package: Some("pkg".to_string()),
+                message_type: vec![DescriptorProto {
+                    name: Some("Foo".to_string()),
+                    field: vec![FieldDescriptorProto {
+                        name: Some("display_name".to_string()),
+                        number: Some(1),
+                        label: Some(field_descriptor_proto::Label::Optional as i32),
+                        r#type: Some(field_descriptor_proto::Type::String as i32),
+                        json_name: Some("displayName".to_string()),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                options: Some(FileOptions {
+                    optimize_for: Some(file_options::OptimizeMode::CodeSize as i32),
+                    ..Default::default()
+                }),
+                syntax: Some("proto3".to_string()),
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn serialize_file_descriptor_set() {
+        let msg = create_file_descriptor_set();
+        let json = serde_json::to_value(&msg).unwrap();
+        let file = &json["file"][0];
+        assert_eq!(file["name"], "pkg/foo.proto");
+        assert_eq!(file["options"]["optimizeFor"], "CODE_SIZE");
+        assert_eq!(file["messageType"][0]["field"][0]["type"], "TYPE_STRING");
+        assert_eq!(file["messageType"][0]["field"][0]["label"], "LABEL_OPTIONAL");
+        assert_eq!(file["messageType"][0]["field"][0]["jsonName"], "displayName");
+        assert!(file.get("dependency").is_none());
+        assert!(file.get("sourceCodeInfo").is_none());
+        let back: FileDescriptorSet = serde_json::from_value(json).unwrap();
+        assert_eq!(back, msg);
+    }
+
+    #[test]
+    fn serialize_uninterpreted_option() {
+        let msg = UninterpretedOption {
+            name: vec![uninterpreted_option::NamePart {
+                name_part: "foo".to_string(),
+                is_extension: true,
+            }],
+            positive_int_value: Some(u64::MAX),
+            double_value: Some(f64::NAN),
+            string_value: Some(b"bar".to_vec()),
+            ..Default::default()
+        };
+        let json = serde_json::to_value(&msg).unwrap();
+        assert_eq!(json["name"][0]["namePart"], "foo");
+        assert_eq!(json["name"][0]["isExtension"], true);
+        assert_eq!(json["positiveIntValue"], "18446744073709551615");
+        assert_eq!(json["doubleValue"], "NaN");
+        assert_eq!(json["stringValue"], "YmFy");
+        let back: UninterpretedOption = serde_json::from_value(json).unwrap();
+        assert_eq!(back.positive_int_value, msg.positive_int_value);
+        assert_eq!(back.string_value, msg.string_value);
+        assert!(back.double_value.unwrap().is_nan());
+
+        // Required fields are written even if they hold the default value.
+        let part = uninterpreted_option::NamePart::default();
+        let json = serde_json::to_value(&part).unwrap();
+        assert_eq!(json, serde_json::json!({"namePart": "", "isExtension": false}));
+    }
+
+    #[test]
+    fn pack_unpack_file_descriptor_set() {
+        let msg = create_file_descriptor_set();
+        let any = Any::try_pack(msg.clone()).unwrap();
+        assert_eq!(any.type_url, "type.googleapis.com/google.protobuf.FileDescriptorSet");
+        let unpacked = any.clone().try_unpack().unwrap();
+        assert_eq!(unpacked.downcast_ref::<FileDescriptorSet>(), Some(&msg));
+
+        let json = serde_json::to_value(&any).unwrap();
+        assert_eq!(json["file"][0]["package"], "pkg");
+        let back: Any = serde_json::from_value(json).unwrap();
+        assert_eq!(back, any);
+    }
+
+    #[test]
+    fn pack_unpack_nested_message() {
+        let msg = descriptor_proto::ReservedRange {
+            start: Some(1),
+            end: Some(10),
+        };
+        let any = Any::try_pack(msg.clone()).unwrap();
+        assert_eq!(
+            any.type_url,
+            "type.googleapis.com/google.protobuf.DescriptorProto.ReservedRange"
+        );
+        let unpacked = any.clone().try_unpack().unwrap();
+        assert_eq!(unpacked.message_name(), "DescriptorProto.ReservedRange");
+        assert_eq!(unpacked.downcast_ref::<descriptor_proto::ReservedRange>(), Some(&msg));
+
+        let json = serde_json::to_value(&any).unwrap();
+        assert_eq!(json["@type"], "type.googleapis.com/google.protobuf.DescriptorProto.ReservedRange");
+        let back: Any = serde_json::from_value(json).unwrap();
+        assert_eq!(back, any);
+    }
+}
@@ -1 +1,464 @@
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use prost_wkt::{FieldMaskable, MergeOptions};
+
+use std::collections::btree_map::{BTreeMap, Entry};
+use std::fmt;
+
 include!(concat!(env!("OUT_DIR"), "/pbmask/google.protobuf.rs"));
+
+/// A trie of field mask paths, split on `.`. A node without children stands for the whole field,
+/// so adding a path that is covered by a parent is a no-op and adding a parent drops its children.
+/// Traversal is in sorted order which gives a normalized list of paths.
+#[derive(Default)]
+struct FieldMaskTree {
+    children: BTreeMap<String, FieldMaskTree>,
+}
+
+impl FieldMaskTree {
+    fn from_paths<'a>(paths: impl IntoIterator<Item = &'a String>) -> Self {
+        let mut tree = FieldMaskTree::default();
+        for path in paths {
+            tree.add_path(path);
+        }
+        tree
+    }
+
+    fn add_path(&mut self, path: &str) {
+        if path.is_empty() {
+            return;
+        }
+        let mut node = self;
+        let mut created = false;
+        for segment in path.split('.') {
+            node = match node.children.entry(segment.to_string()) {
+                Entry::Occupied(entry) => {
+                    let child = entry.into_mut();
+                    if !created && child.children.is_empty() {
+                        // Already covered by this path or one of its parents.
+                        return;
+                    }
+                    child
+                }
+                Entry::Vacant(entry) => {
+                    created = true;
+                    entry.insert(FieldMaskTree::default())
+                }
+            };
+        }
+        node.children.clear();
+    }
+
+    fn remove_path(&mut self, path: &str) {
+        let segments: Vec<&str> = path.split('.').collect();
+        self.remove_segments(&segments);
+    }
+
+    // Returns `true` if this node lost its last child and should be removed by its parent.
+    fn remove_segments(&mut self, segments: &[&str]) -> bool {
+        let (first, rest) = match segments.split_first() {
+            Some(split) => split,
+            None => return false,
+        };
+        let remove = match self.children.get_mut(*first) {
+            Some(_) if rest.is_empty() => true,
+            Some(child) if child.children.is_empty() => false,
+            Some(child) => child.remove_segments(rest),
+            None => false,
+        };
+        if remove {
+            self.children.remove(*first);
+            return self.children.is_empty();
+        }
+        false
+    }
+
+    fn contains(&self, path: &str) -> bool {
+        let mut node = self;
+        for segment in path.split('.') {
+            match node.children.get(segment) {
+                Some(child) if child.children.is_empty() => return true,
+                Some(child) => node = child,
+                None => return false,
+            }
+        }
+        false
+    }
+
+    // Adds the parts of `path` that are covered by this tree to `output`.
+    fn intersect_path(&self, path: &str, output: &mut FieldMaskTree) {
+        if self.children.is_empty() || path.is_empty() {
+            return;
+        }
+        let mut node = self;
+        for segment in path.split('.') {
+            match node.children.get(segment) {
+                Some(child) if child.children.is_empty() => {
+                    output.add_path(path);
+                    return;
+                }
+                Some(child) => node = child,
+                None => return,
+            }
+        }
+        let mut paths = Vec::new();
+        node.collect_paths(path, &mut paths);
+        for path in &paths {
+            output.add_path(path);
+        }
+    }
+
+    fn collect_paths(&self, prefix: &str, paths: &mut Vec<String>) {
+        if self.children.is_empty() {
+            if !prefix.is_empty() {
+                paths.push(prefix.to_string());
+            }
+            return;
+        }
+        for (segment, child) in &self.children {
+            let path = if prefix.is_empty() {
+                segment.clone()
+            } else {
+                format!("{prefix}.{segment}")
+            };
+            child.collect_paths(&path, paths);
+        }
+    }
+
+    fn into_field_mask(self) -> FieldMask {
+        let mut paths = Vec::new();
+        self.collect_paths("", &mut paths);
+        FieldMask { paths }
+    }
+}
+
+impl FieldMask {
+    /// Creates a `FieldMask` from the given paths, e.g. `["foo_bar", "baz.qux"]`.
+    pub fn from_paths<I, S>(paths: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        FieldMask {
+            paths: paths.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Normalizes the mask: paths are sorted, duplicates are removed and paths covered by one of
+    /// their parents (e.g. `foo.bar` when `foo` is present) are dropped.
+    pub fn normalize(&mut self) {
+        *self = FieldMaskTree::from_paths(&self.paths).into_field_mask();
+    }
+
+    /// Returns a normalized mask covering every path of either mask.
+    pub fn union(&self, other: &FieldMask) -> FieldMask {
+        FieldMaskTree::from_paths(self.paths.iter().chain(&other.paths)).into_field_mask()
+    }
+
+    /// Returns a normalized mask covering only the paths present in both masks.
+    pub fn intersection(&self, other: &FieldMask) -> FieldMask {
+        let tree = FieldMaskTree::from_paths(&self.paths);
+        let mut result = FieldMaskTree::default();
+        for path in &other.paths {
+            tree.intersect_path(path, &mut result);
+        }
+        result.into_field_mask()
+    }
+
+    /// Returns a normalized mask with the paths of `other` removed. Removing a sub path of a
+    /// path in this mask, e.g. `foo.bar` from `foo`, leaves the parent path untouched.
+    pub fn subtract(&self, other: &FieldMask) -> FieldMask {
+        let mut tree = FieldMaskTree::from_paths(&self.paths);
+        for path in &other.paths {
+            tree.remove_path(path);
+        }
+        tree.into_field_mask()
+    }
+
+    /// Returns `true` if `path`, or one of its parents, is part of this mask. This compares `path`
+    /// against every path of the mask without allocating; use `contains_all` to look up many paths
+    /// in a large mask.
+    pub fn contains(&self, path: &str) -> bool {
+        self.paths.iter().any(|mask_path| {
+            !mask_path.is_empty()
+                && path.starts_with(mask_path.as_str())
+                && (path.len() == mask_path.len() || path[mask_path.len()..].starts_with('.'))
+        })
+    }
+
+    /// Returns `true` if every one of `paths` is covered by this mask, see `contains`. The mask is
+    /// turned into a trie once, so each lookup only walks the segments of the path.
+    pub fn contains_all(&self, paths: &[&str]) -> bool {
+        let tree = FieldMaskTree::from_paths(&self.paths);
+        paths.iter().all(|path| tree.contains(path))
+    }
+
+    /// Returns `true` if every path of this mask names a field of message `M`.
+    pub fn is_valid_for<M: FieldMaskable>(&self) -> bool {
+        self.paths.iter().all(|path| M::is_valid_path(path))
+    }
+
+    /// Copies the fields covered by this mask from `src` into `dst`. Message fields are merged and
+    /// repeated fields are appended to; see `merge_with_options` to replace them instead.
+    pub fn merge<M: FieldMaskable>(&self, src: &M, dst: &mut M) {
+        self.merge_with_options(src, dst, &MergeOptions::default())
+    }
+
+    /// Copies the fields covered by this mask from `src` into `dst` using the given options.
+    /// Members of a oneof are always replaced as a whole.
+    pub fn merge_with_options<M: FieldMaskable>(&self, src: &M, dst: &mut M, options: &MergeOptions) {
+        let mut mask = self.clone();
+        mask.normalize();
+        for path in &mask.paths {
+            dst.merge_path(src, path, options);
+        }
+    }
+
+    /// Clears every field of `msg` that is not covered by this mask.
+    pub fn trim<M: FieldMaskable>(&self, msg: &mut M) {
+        let mut mask = self.clone();
+        mask.normalize();
+        let paths: Vec<&str> = mask.paths.iter().map(String::as_str).collect();
+        msg.trim_paths(&paths);
+    }
+}
+
+/// Converts a snake_case path such as `foo_bar.baz` to its lowerCamelCase JSON form `fooBar.baz`.
+/// Returns `None` if the path cannot be converted back to the original, e.g. because it contains
+/// upper case characters or an underscore that is not followed by a lower case letter.
+fn path_to_json(path: &str) -> Option<String> {
+    let mut json = String::with_capacity(path.len());
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '_' => match chars.next() {
+                Some(next) if next.is_ascii_lowercase() => json.push(next.to_ascii_uppercase()),
+                _ => return None,
+            },
+            c if c.is_ascii_uppercase() => return None,
+            c => json.push(c),
+        }
+    }
+    Some(json)
+}
+
+/// Converts a lowerCamelCase JSON path such as `fooBar.baz` back to its snake_case form
+/// `foo_bar.baz`. Returns `None` if the path contains underscores or empty segments.
+fn path_from_json(path: &str) -> Option<String> {
+    if path.contains('_') || path.split('.').any(str::is_empty) {
+        return None;
+    }
+    let mut snake = String::with_capacity(path.len() + 4);
+    for c in path.chars() {
+        if c.is_ascii_uppercase() {
+            snake.push('_');
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    Some(snake)
+}
+
+/// Serializes the mask as a single comma separated string of lowerCamelCase paths, as required
+/// by the proto3 JSON mapping, e.g. `"fooBar,baz.quxValue"`.
+impl Serialize for FieldMask {
+    fn serialize<S>(&self, serializer: S) -> Result<<S as Serializer>::Ok, <S as Serializer>::Error>
+    where
+        S: Serializer,
+    {
+        let paths = self
+            .paths
+            .iter()
+            .map(|path| {
+                path_to_json(path).ok_or_else(|| {
+                    serde::ser::Error::custom(format!(
+                        "FieldMask path {path} cannot be converted to lowerCamelCase"
+                    ))
+                })
+            })
+            .collect::<Result<Vec<String>, S::Error>>()?;
+        serializer.serialize_str(&paths.join(","))
+    }
+}
+
+impl<'de> Deserialize<'de> for FieldMask {
+    fn deserialize<D>(deserializer: D) -> Result<Self, <D as Deserializer<'de>>::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct FieldMaskVisitor;
+
+        impl<'de> Visitor<'de> for FieldMaskVisitor {
+            type Value = FieldMask;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("FieldMask as a comma separated string of lowerCamelCase paths")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                if value.is_empty() {
+                    return Ok(FieldMask::default());
+                }
+                let paths = value
+                    .split(',')
+                    .map(|path| {
+                        path_from_json(path).ok_or_else(|| {
+                            de::Error::custom(format!("Invalid FieldMask path {path}"))
+                        })
+                    })
+                    .collect::<Result<Vec<String>, E>>()?;
+                Ok(FieldMask { paths })
+            }
+        }
+        deserializer.deserialize_str(FieldMaskVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::pbmask::*;
+
+    #[test]
+    fn serialize_field_mask() {
+        let mask = FieldMask {
+            paths: vec!["foo_bar".to_string(), "baz".to_string(), "user.display_name".to_string()],
+        };
+        let json = serde_json::to_string(&mask).unwrap();
+        assert_eq!(json, "\"fooBar,baz,user.displayName\"");
+        let back: FieldMask = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, mask);
+    }
+
+    #[test]
+    fn empty_field_mask() {
+        let mask = FieldMask::default();
+        let json = serde_json::to_string(&mask).unwrap();
+        assert_eq!(json, "\"\"");
+        let back: FieldMask = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, mask);
+    }
+
+    #[test]
+    fn normalize_field_mask() {
+        let mut mask = FieldMask::from_paths(["foo.bar", "baz", "foo", "baz", "qux.quux"]);
+        mask.normalize();
+        assert_eq!(mask.paths, vec!["baz", "foo", "qux.quux"]);
+    }
+
+    #[test]
+    fn union_field_mask() {
+        let first = FieldMask::from_paths(["foo.bar", "baz"]);
+        let second = FieldMask::from_paths(["foo", "qux.quux"]);
+        assert_eq!(first.union(&second).paths, vec!["baz", "foo", "qux.quux"]);
+    }
+
+    #[test]
+    fn intersection_field_mask() {
+        let first = FieldMask::from_paths(["foo", "bar.baz", "bar.qux", "quux"]);
+        let second = FieldMask::from_paths(["foo.bar", "bar", "corge"]);
+        assert_eq!(
+            first.intersection(&second).paths,
+            vec!["bar.baz", "bar.qux", "foo.bar"]
+        );
+    }
+
+    #[test]
+    fn subtract_field_mask() {
+        let first = FieldMask::from_paths(["foo", "bar.baz", "bar.qux", "quux"]);
+        let second = FieldMask::from_paths(["foo.bar", "bar.baz", "bar.qux", "quux"]);
+        assert_eq!(first.subtract(&second).paths, vec!["foo"]);
+    }
+
+    #[test]
+    fn contains_field_mask() {
+        let mask = FieldMask::from_paths(["foo", "bar.baz"]);
+        assert!(mask.contains("foo"));
+        assert!(mask.contains("foo.bar"));
+        assert!(mask.contains("bar.baz.qux"));
+        assert!(!mask.contains("bar"));
+        assert!(!mask.contains("baz"));
+        assert!(!mask.contains("foobar"));
+        assert!(!mask.contains("bar.bazqux"));
+        assert!(!mask.contains(""));
+        assert!(mask.contains_all(&["foo.bar", "bar.baz"]));
+        assert!(!mask.contains_all(&["foo", "bar"]));
+        assert!(!mask.contains_all(&["foobar"]));
+    }
+
+    #[test]
+    fn field_mask_is_valid_for() {
+        assert!(FieldMask::from_paths(["seconds", "nanos"]).is_valid_for::<crate::Duration>());
+        assert!(!FieldMask::from_paths(["seconds", "minutes"]).is_valid_for::<crate::Duration>());
+        assert!(!FieldMask::from_paths(["seconds.value"]).is_valid_for::<crate::Timestamp>());
+    }
+
+    #[test]
+    fn merge_field_mask() {
+        let src = crate::Timestamp { seconds: 10, nanos: 20 };
+        let mut dst = crate::Timestamp { seconds: 1, nanos: 2 };
+        FieldMask::from_paths(["nanos"]).merge(&src, &mut dst);
+        assert_eq!(dst, crate::Timestamp { seconds: 1, nanos: 20 });
+    }
+
+    #[test]
+    fn merge_field_mask_map() {
+        let src = crate::Struct {
+            fields: [("foo".to_string(), crate::Value::from(1.0))].into(),
+        };
+        let mut dst = crate::Struct {
+            fields: [("bar".to_string(), crate::Value::from(2.0))].into(),
+        };
+        let mask = FieldMask::from_paths(["fields"]);
+        mask.merge(&src, &mut dst);
+        assert_eq!(dst.fields.len(), 2);
+
+        let mut options = MergeOptions::default();
+        options.replace_repeated_fields = true;
+        mask.merge_with_options(&src, &mut dst, &options);
+        assert_eq!(dst, src);
+    }
+
+    #[test]
+    fn merge_field_mask_oneof() {
+        let src = crate::Value::from(1.0);
+        let mut dst = crate::Value::from("hello".to_string());
+        FieldMask::from_paths(["bool_value"]).merge(&src, &mut dst);
+        assert_eq!(dst, crate::Value::from("hello".to_string()));
+        FieldMask::from_paths(["string_value"]).merge(&src, &mut dst);
+        assert_eq!(dst, crate::Value { kind: None });
+        FieldMask::from_paths(["number_value"]).merge(&src, &mut dst);
+        assert_eq!(dst, src);
+    }
+
+    #[test]
+    fn trim_field_mask() {
+        let mut ts = crate::Timestamp { seconds: 10, nanos: 20 };
+        FieldMask::from_paths(["seconds"]).trim(&mut ts);
+        assert_eq!(ts, crate::Timestamp { seconds: 10, nanos: 0 });
+
+        let mut value = crate::Value::from(1.0);
+        FieldMask::from_paths(["number_value"]).trim(&mut value);
+        assert_eq!(value, crate::Value::from(1.0));
+        FieldMask::from_paths(["string_value"]).trim(&mut value);
+        assert_eq!(value, crate::Value { kind: None });
+    }
+
+    #[test]
+    fn reject_invalid_paths() {
+        for path in ["fooBar", "foo__bar", "foo_1", "foo_"] {
+            let mask = FieldMask {
+                paths: vec![path.to_string()],
+            };
+            assert!(serde_json::to_string(&mask).is_err(), "{path} should not serialize");
+        }
+        for json in ["\"foo_bar\"", "\"foo,,bar\"", "\"foo..bar\""] {
+            assert!(serde_json::from_str::<FieldMask>(json).is_err(), "{json} should not deserialize");
+        }
+    }
+}
@@ -7,9 +7,20 @@ use std::fmt;
 
 include!(concat!(env!("OUT_DIR"), "/pbstruct/google.protobuf.rs"));
 
+/// Error returned when converting a `Value` into a Rust type fails.
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct ValueError {
-    description: Cow<'static, str>,
+#[non_exhaustive]
+pub enum ValueError {
+    /// The `Value` holds a different kind than the one required by the target type.
+    UnexpectedKind {
+        target: &'static str,
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// The `Value` holds no kind at all.
+    Empty { target: &'static str },
+    /// Any other error, as created by `ValueError::new`.
+    Other(Cow<'static, str>),
 }
 
 impl ValueError {
@@ -17,22 +28,50 @@ impl ValueError {
     where
         S: Into<Cow<'static, str>>,
     {
-        ValueError {
-            description: description.into(),
-        }
+        ValueError::Other(description.into())
     }
-}
 
-impl std::error::Error for ValueError {
-    fn description(&self) -> &str {
-        &self.description
+    fn unexpected_kind(target: &'static str, expected: &'static str, found: &value::Kind) -> Self {
+        ValueError::UnexpectedKind {
+            target,
+            expected,
+            found: found.name(),
+        }
     }
 }
 
+impl std::error::Error for ValueError {}
+
 impl std::fmt::Display for ValueError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str("failed to convert Value: ")?;
-        f.write_str(&self.description)
+        match self {
+            ValueError::UnexpectedKind {
+                target,
+                expected,
+                found,
+            } => write!(
+                f,
+                "cannot convert to {target} because this is a {found}, not a {expected}"
+            ),
+            ValueError::Empty { target } => {
+                write!(f, "conversion to {target} failed because value is empty")
+            }
+            ValueError::Other(description) => f.write_str(description),
+        }
+    }
+}
+
+impl value::Kind {
+    fn name(&self) -> &'static str {
+        match self {
+            value::Kind::NullValue(_) => "NullValue",
+            value::Kind::NumberValue(_) => "NumberValue",
+            value::Kind::StringValue(_) => "StringValue",
+            value::Kind::BoolValue(_) => "BoolValue",
+            value::Kind::StructValue(_) => "StructValue",
+            value::Kind::ListValue(_) => "ListValue",
+        }
     }
 }
 
@@ -77,12 +116,14 @@ impl TryFrom<Value> for f64 {
     fn try_from(value: Value) -> Result<Self, Self::Error> {
         match value.kind {
             Some(value::Kind::NumberValue(num)) => Ok(num),
-            Some(_other) => Err(ValueError::new(
-                "Cannot convert to f64 because this is not a ValueNumber.",
-            )),
-            _ => Err(ValueError::new(
-                "Conversion to f64 failed because value is empty!",
+            Some(other) => Err(ValueError::unexpected_kind(
+                "f64",
+                "NumberValue",
+                &other,
             )),
+            None => Err(ValueError::Empty {
+                target: "f64",
+            }),
         }
     }
 }
@@ -100,12 +141,14 @@ impl TryFrom<Value> for String {
     fn try_from(value: Value) -> Result<Self, Self::Error> {
         match value.kind {
             Some(value::Kind::StringValue(string)) => Ok(string),
-            Some(_other) => Err(ValueError::new(
-                "Cannot convert to String because this is not a StringValue.",
-            )),
-            _ => Err(ValueError::new(
-                "Conversion to String failed because value is empty!",
+            Some(other) => Err(ValueError::unexpected_kind(
+                "String",
+                "StringValue",
+                &other,
             )),
+            None => Err(ValueError::Empty {
+                target: "String",
+            }),
         }
     }
 }
@@ -123,12 +166,14 @@ impl TryFrom<Value> for bool {
     fn try_from(value: Value) -> Result<Self, Self::Error> {
         match value.kind {
             Some(value::Kind::BoolValue(b)) => Ok(b),
-            Some(_other) => Err(ValueError::new(
-                "Cannot convert to bool because this is not a BoolValue.",
-            )),
-            _ => Err(ValueError::new(
-                "Conversion to bool failed because value is empty!",
+            Some(other) => Err(ValueError::unexpected_kind(
+                "bool",
+                "BoolValue",
+                &other,
             )),
+            None => Err(ValueError::Empty {
+                target: "bool",
+            }),
         }
     }
 }
@@ -147,12 +192,14 @@ impl TryFrom<Value> for std::collections::HashMap<std::string::String, Value> {
     fn try_from(value: Value) -> Result<Self, Self::Error> {
         match value.kind {
             Some(value::Kind::StructValue(s)) => Ok(s.fields),
-            Some(_other) => Err(ValueError::new(
-                "Cannot convert to HashMap<String, Value> because this is not a StructValue.",
-            )),
-            _ => Err(ValueError::new(
-                "Conversion to HashMap<String, Value> failed because value is empty!",
+            Some(other) => Err(ValueError::unexpected_kind(
+                "HashMap<String, Value>",
+                "StructValue",
+                &other,
             )),
+            None => Err(ValueError::Empty {
+                target: "HashMap<String, Value>",
+            }),
         }
     }
 }
@@ -171,12 +218,14 @@ impl TryFrom<Value> for std::vec::Vec<Value> {
     fn try_from(value: Value) -> Result<Self, Self::Error> {
         match value.kind {
             Some(value::Kind::ListValue(list)) => Ok(list.values),
-            Some(_other) => Err(ValueError::new(
-                "Cannot convert to Vec<Value> because this is not a ListValue.",
-            )),
-            _ => Err(ValueError::new(
-                "Conversion to Vec<Value> failed because value is empty!",
+            Some(other) => Err(ValueError::unexpected_kind(
+                "Vec<Value>",
+                "ListValue",
+                &other,
             )),
+            None => Err(ValueError::Empty {
+                target: "Vec<Value>",
+            }),
         }
     }
 }
@@ -435,4 +484,27 @@ mod tests {
         let back: serde_json::Value = serde_json::from_str(&string).unwrap();
         assert_eq!(sj, back);
     }
+
+    #[test]
+    fn conversion_error_test() {
+        let err = f64::try_from(Value::from(String::from("ten"))).unwrap_err();
+        assert_eq!(
+            err,
+            ValueError::UnexpectedKind {
+                target: "f64",
+                expected: "NumberValue",
+                found: "StringValue",
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "failed to convert Value: cannot convert to f64 because this is a StringValue, not a NumberValue"
+        );
+
+        let err = bool::try_from(Value { kind: None }).unwrap_err();
+        assert_eq!(err, ValueError::Empty { target: "bool" });
+
+        let err = ValueError::new("custom");
+        assert_eq!(err.to_string(), "failed to convert Value: custom");
+    }
 }
@@ -111,6 +111,86 @@ impl<'de> Deserialize<'de> for Timestamp {
     }
 }
 
+/// The largest duration, in seconds, allowed by the proto3 JSON mapping (roughly 10,000 years).
+const DURATION_MAX_SECONDS: i64 = 315_576_000_000;
+
+/// Serializes the duration as its proto3 JSON string, e.g. `"1.500s"` or `"-0.000000100s"`,
+/// using the `Display` format.
+impl Serialize for Duration {
+    fn serialize<S>(&self, serializer: S) -> Result<<S as Serializer>::Ok, <S as Serializer>::Error>
+    where
+        S: Serializer,
+    {
+        let mut d = self.clone();
+        d.normalize();
+        if d.seconds.abs() > DURATION_MAX_SECONDS {
+            return Err(serde::ser::Error::custom(format!(
+                "Duration {self:?} is out of range for JSON"
+            )));
+        }
+        serializer.serialize_str(&d.to_string())
+    }
+}
+
+/// Deserializes the duration from its proto3 JSON string. With the `legacy-duration` feature
+/// enabled the `{"seconds": .., "nanos": ..}` object written by earlier versions is accepted too.
+impl<'de> Deserialize<'de> for Duration {
+    fn deserialize<D>(deserializer: D) -> Result<Self, <D as Deserializer<'de>>::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct DurationVisitor;
+
+        impl<'de> Visitor<'de> for DurationVisitor {
+            type Value = Duration;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("Duration in proto3 JSON format, e.g. \"1.5s\"")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                let duration = Duration::from_str(value).map_err(|err| {
+                    serde::de::Error::custom(format!(
+                        "Failed to parse {value} as duration: {err}"
+                    ))
+                })?;
+                if duration.seconds.abs() > DURATION_MAX_SECONDS {
+                    return Err(serde::de::Error::custom(format!(
+                        "Duration {value} is out of range"
+                    )));
+                }
+                Ok(duration)
+            }
+
+            #[cfg(feature = "legacy-duration")]
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: de::MapAccess<'de>,
+            {
+                let mut duration = Duration::default();
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "seconds" => duration.seconds = map.next_value()?,
+                        "nanos" => duration.nanos = map.next_value()?,
+                        _ => {
+                            map.next_value::<de::IgnoredAny>()?;
+                        }
+                    }
+                }
+                Ok(duration)
+            }
+        }
+
+        #[cfg(feature = "legacy-duration")]
+        return deserializer.deserialize_any(DurationVisitor);
+        #[cfg(not(feature = "legacy-duration"))]
+        deserializer.deserialize_str(DurationVisitor)
+    }
+}
+
 #[cfg(feature = "diesel")]
 impl FromSql<diesel::sql_types::Timestamp, Pg> for Timestamp {
     fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
@@ -173,6 +253,55 @@ mod tests {
         assert_eq!(duration, back);
     }
 
+    #[test]
+    fn duration_json_format() {
+        let cases = [
+            (0, 0, "\"0s\""),
+            (10, 0, "\"10s\""),
+            (10, 100, "\"10.000000100s\""),
+            (1, 500_000_000, "\"1.500s\""),
+            (1, 5_000, "\"1.000005s\""),
+            (-1, 0, "\"-1s\""),
+            (0, -500_000_000, "\"-0.500s\""),
+            (-3, -1_000, "\"-3.000001s\""),
+        ];
+        for (seconds, nanos, expected) in cases {
+            let duration = Duration { seconds, nanos };
+            let json = serde_json::to_string(&duration).expect("json");
+            assert_eq!(json, expected);
+            let back: Duration = serde_json::from_str(&json).expect("duration");
+            assert_eq!(back, duration);
+        }
+    }
+
+    #[test]
+    fn duration_display_matches_json() {
+        for (seconds, nanos) in [(-1, 0), (0, -500_000_000), (-3, -1_000), (-2, 999_999_999)] {
+            let duration = Duration { seconds, nanos };
+            let json = serde_json::to_string(&duration).expect("json");
+            assert_eq!(json, format!("\"{duration}\""));
+        }
+    }
+
+    #[test]
+    fn invalid_duration_json() {
+        assert!(serde_json::from_str::<Duration>("\"10\"").is_err());
+        assert!(serde_json::from_str::<Duration>("\"1.0000000001s\"").is_err());
+        assert!(serde_json::from_str::<Duration>("\"315576000001s\"").is_err());
+        let too_long = Duration { seconds: 315_576_000_001, nanos: 0 };
+        assert!(serde_json::to_string(&too_long).is_err());
+    }
+
+    #[test]
+    fn duration_object_json() {
+        let result = serde_json::from_str::<Duration>(r#"{"seconds":10,"nanos":100}"#);
+        if cfg!(feature = "legacy-duration") {
+            assert_eq!(result.unwrap(), Duration { seconds: 10, nanos: 100 });
+        } else {
+            assert!(result.is_err());
+        }
+    }
+
     #[test]
     fn invalid_timestamp_test() {
         let ts = Timestamp {
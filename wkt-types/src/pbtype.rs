@@ -0,0 +1,76 @@
+include!(concat!(env!("OUT_DIR"), "/pbtype/google.protobuf.rs"));
+
+#[cfg(test)]
+mod tests {
+
+    use crate::pbtype::*;
+    use crate::Any;
+
+    fn create_type() -> Type {
+        Type {
+            name: "pkg.Foo".to_string(),
+            fields: vec![Field {
+                kind: field::Kind::TypeString as i32,
+                cardinality: field::Cardinality::Optional as i32,
+                number: 1,
+                name: "display_name".to_string(),
+                json_name: "displayName".to_string(),
+                ..Default::default()
+            }],
+            source_context: Some(SourceContext {
+                file_name: "pkg/foo.proto".to_string(),
+            }),
+            syntax: Syntax::Proto3 as i32,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn serialize_type() {
+        let msg = create_type();
+        let json = serde_json::to_value(&msg).unwrap();
+        assert_eq!(json["syntax"], "SYNTAX_PROTO3");
+        assert_eq!(json["sourceContext"]["fileName"], "pkg/foo.proto");
+        assert_eq!(json["fields"][0]["kind"], "TYPE_STRING");
+        assert_eq!(json["fields"][0]["cardinality"], "CARDINALITY_OPTIONAL");
+        assert_eq!(json["fields"][0]["jsonName"], "displayName");
+        let back: Type = serde_json::from_value(json).unwrap();
+        assert_eq!(back, msg);
+    }
+
+    #[test]
+    fn deserialize_enum_number() {
+        let api: Api = serde_json::from_str(r#"{"name":"pkg.Service","syntax":1}"#).unwrap();
+        assert_eq!(api.syntax(), Syntax::Proto3);
+        assert!(serde_json::from_str::<Api>(r#"{"syntax":"PROTO3"}"#).is_err());
+    }
+
+    #[test]
+    fn pack_unpack_api() {
+        let api = Api {
+            name: "pkg.Service".to_string(),
+            methods: vec![Method {
+                name: "Get".to_string(),
+                request_type_url: "type.googleapis.com/pkg.GetRequest".to_string(),
+                response_type_url: "type.googleapis.com/pkg.Foo".to_string(),
+                ..Default::default()
+            }],
+            version: "v1".to_string(),
+            ..Default::default()
+        };
+        let any = Any::try_pack(api.clone()).unwrap();
+        assert_eq!(any.type_url, "type.googleapis.com/google.protobuf.Api");
+        let unpacked = any.try_unpack().unwrap();
+        assert_eq!(unpacked.downcast_ref::<Api>(), Some(&api));
+    }
+
+    #[test]
+    fn serialize_type_in_any() {
+        let any = Any::try_pack(create_type()).unwrap();
+        let json = serde_json::to_value(&any).unwrap();
+        assert_eq!(json["@type"], "type.googleapis.com/google.protobuf.Type");
+        assert_eq!(json["name"], "pkg.Foo");
+        let back: Any = serde_json::from_value(json).unwrap();
+        assert_eq!(back, any);
+    }
+}
@@ -0,0 +1,177 @@
+use prost_wkt::json::JsonScalar;
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+
+include!(concat!(env!("OUT_DIR"), "/pbwrappers/google.protobuf.rs"));
+
+macro_rules! wrapper_conversions {
+    ($wrapper:ident, $ty:ty) => {
+        impl From<$ty> for $wrapper {
+            fn from(value: $ty) -> Self {
+                $wrapper { value }
+            }
+        }
+
+        impl From<$wrapper> for $ty {
+            fn from(wrapper: $wrapper) -> Self {
+                wrapper.value
+            }
+        }
+    };
+}
+
+wrapper_conversions!(DoubleValue, f64);
+wrapper_conversions!(FloatValue, f32);
+wrapper_conversions!(Int64Value, i64);
+wrapper_conversions!(UInt64Value, u64);
+wrapper_conversions!(Int32Value, i32);
+wrapper_conversions!(UInt32Value, u32);
+wrapper_conversions!(BoolValue, bool);
+wrapper_conversions!(StringValue, String);
+wrapper_conversions!(BytesValue, Vec<u8>);
+
+impl From<&str> for StringValue {
+    fn from(value: &str) -> Self {
+        StringValue {
+            value: value.to_string(),
+        }
+    }
+}
+
+/// Writes the wrapped value like a field of its type, using `prost_wkt::json`: 64 bit integers
+/// and bytes as strings, and NaN and the infinities as `"NaN"`, `"Infinity"` and `"-Infinity"`.
+macro_rules! wrapper_serde {
+    ($wrapper:ident) => {
+        impl Serialize for $wrapper {
+            fn serialize<S>(&self, serializer: S) -> Result<<S as Serializer>::Ok, <S as Serializer>::Error>
+            where
+                S: Serializer,
+            {
+                self.value.serialize_json(serializer)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $wrapper {
+            fn deserialize<D>(deserializer: D) -> Result<Self, <D as Deserializer<'de>>::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                JsonScalar::deserialize_json(deserializer).map(|value| $wrapper { value })
+            }
+        }
+    };
+}
+
+wrapper_serde!(DoubleValue);
+wrapper_serde!(FloatValue);
+wrapper_serde!(Int64Value);
+wrapper_serde!(UInt64Value);
+wrapper_serde!(Int32Value);
+wrapper_serde!(UInt32Value);
+wrapper_serde!(BytesValue);
+
+impl Serialize for BoolValue {
+    fn serialize<S>(&self, serializer: S) -> Result<<S as Serializer>::Ok, <S as Serializer>::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bool(self.value)
+    }
+}
+
+impl<'de> Deserialize<'de> for BoolValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, <D as Deserializer<'de>>::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        bool::deserialize(deserializer).map(BoolValue::from)
+    }
+}
+
+impl Serialize for StringValue {
+    fn serialize<S>(&self, serializer: S) -> Result<<S as Serializer>::Ok, <S as Serializer>::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.value)
+    }
+}
+
+impl<'de> Deserialize<'de> for StringValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, <D as Deserializer<'de>>::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer).map(StringValue::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::pbwrappers::*;
+
+    #[test]
+    fn serialize_numbers() {
+        assert_eq!(serde_json::to_string(&DoubleValue::from(1.5)).unwrap(), "1.5");
+        assert_eq!(serde_json::to_string(&FloatValue::from(f32::NAN)).unwrap(), "\"NaN\"");
+        assert_eq!(serde_json::to_string(&DoubleValue::from(f64::INFINITY)).unwrap(), "\"Infinity\"");
+        assert_eq!(serde_json::to_string(&DoubleValue::from(f64::NEG_INFINITY)).unwrap(), "\"-Infinity\"");
+        assert_eq!(serde_json::to_string(&Int64Value::from(-10)).unwrap(), "\"-10\"");
+        assert_eq!(serde_json::to_string(&UInt64Value::from(u64::MAX)).unwrap(), "\"18446744073709551615\"");
+        assert_eq!(serde_json::to_string(&Int32Value::from(-10)).unwrap(), "-10");
+        assert_eq!(serde_json::to_string(&UInt32Value::from(10)).unwrap(), "10");
+    }
+
+    #[test]
+    fn deserialize_numbers() {
+        let double: DoubleValue = serde_json::from_str("\"-Infinity\"").unwrap();
+        assert_eq!(f64::from(double), f64::NEG_INFINITY);
+        let float: FloatValue = serde_json::from_str("\"NaN\"").unwrap();
+        assert!(f32::from(float).is_nan());
+        let int64: Int64Value = serde_json::from_str("\"-9223372036854775808\"").unwrap();
+        assert_eq!(i64::from(int64), i64::MIN);
+        let int64: Int64Value = serde_json::from_str("42").unwrap();
+        assert_eq!(i64::from(int64), 42);
+        let uint32: UInt32Value = serde_json::from_str("\"7\"").unwrap();
+        assert_eq!(u32::from(uint32), 7);
+        let int32: Int32Value = serde_json::from_str("1e2").unwrap();
+        assert_eq!(i32::from(int32), 100);
+    }
+
+    #[test]
+    fn reject_invalid_numbers() {
+        assert!(serde_json::from_str::<Int32Value>("2147483648").is_err());
+        assert!(serde_json::from_str::<UInt64Value>("-1").is_err());
+        assert!(serde_json::from_str::<Int64Value>("1.5").is_err());
+        assert!(serde_json::from_str::<UInt64Value>("1e20").is_err());
+        assert!(serde_json::from_str::<UInt64Value>("1.8446744073709552e19").is_err());
+        assert!(serde_json::from_str::<Int64Value>("-1e19").is_err());
+        assert!(serde_json::from_str::<Int64Value>("9.223372036854775808e18").is_err());
+        assert_eq!(i64::from(serde_json::from_str::<Int64Value>("-9.223372036854775808e18").unwrap()), i64::MIN);
+        assert_eq!(u64::from(serde_json::from_str::<UInt64Value>("1e19").unwrap()), 10_000_000_000_000_000_000);
+        assert!(serde_json::from_str::<FloatValue>("1e40").is_err());
+        assert!(serde_json::from_str::<DoubleValue>("\"one\"").is_err());
+    }
+
+    #[test]
+    fn serialize_bool_and_string() {
+        let json = serde_json::to_string(&BoolValue::from(true)).unwrap();
+        assert_eq!(json, "true");
+        assert!(bool::from(serde_json::from_str::<BoolValue>(&json).unwrap()));
+        let json = serde_json::to_string(&StringValue::from("hello")).unwrap();
+        assert_eq!(json, "\"hello\"");
+        assert_eq!(String::from(serde_json::from_str::<StringValue>(&json).unwrap()), "hello");
+    }
+
+    #[test]
+    fn serialize_bytes() {
+        let bytes = BytesValue::from(vec![0xfb, 0xff, 0x01]);
+        let json = serde_json::to_string(&bytes).unwrap();
+        assert_eq!(json, "\"+/8B\"");
+        assert_eq!(serde_json::from_str::<BytesValue>(&json).unwrap(), bytes);
+        assert_eq!(serde_json::from_str::<BytesValue>("\"-_8B\"").unwrap(), bytes);
+        assert_eq!(serde_json::from_str::<BytesValue>("\"aGk=\"").unwrap(), BytesValue::from(b"hi".to_vec()));
+        assert!(serde_json::from_str::<BytesValue>("\"!!\"").is_err());
+    }
+}
@@ -0,0 +1,152 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Field attributes as generated by `prost_wkt_build::add_json_attributes`.
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Numbers {
+    #[serde(rename = "big", skip_serializing_if = "::prost_wkt::json::is_default", with = "::prost_wkt::json::scalar")]
+    #[prost(int64, tag = "1")]
+    pub big: i64,
+    #[serde(rename = "maybe", skip_serializing_if = "::prost_wkt::json::is_default", with = "::prost_wkt::json::optional")]
+    #[prost(uint64, optional, tag = "2")]
+    pub maybe: ::core::option::Option<u64>,
+    #[serde(rename = "many", skip_serializing_if = "::prost_wkt::json::is_default", with = "::prost_wkt::json::repeated")]
+    #[prost(sint64, repeated, tag = "3")]
+    pub many: ::prost::alloc::vec::Vec<i64>,
+    #[serde(rename = "rawData", alias = "raw_data", skip_serializing_if = "::prost_wkt::json::is_default", with = "::prost_wkt::json::scalar")]
+    #[prost(bytes = "vec", tag = "4")]
+    pub raw_data: ::prost::alloc::vec::Vec<u8>,
+    #[serde(rename = "counts", skip_serializing_if = "::prost_wkt::json::is_default", with = "::prost_wkt::json::map")]
+    #[prost(map = "string, int64", tag = "5")]
+    pub counts: HashMap<::prost::alloc::string::String, i64>,
+    #[serde(rename = "smallValue", alias = "small_value", skip_serializing_if = "::prost_wkt::json::is_default")]
+    #[prost(int32, tag = "6")]
+    pub small_value: i32,
+    #[serde(rename = "customName", alias = "label", skip_serializing_if = "::prost_wkt::json::is_default")]
+    #[prost(string, tag = "7")]
+    pub label: ::prost::alloc::string::String,
+}
+
+#[test]
+fn test_json_mapping() {
+    let numbers = Numbers {
+        big: i64::MIN,
+        maybe: Some(0),
+        many: vec![1, -2],
+        raw_data: vec![0xfb, 0xff],
+        counts: HashMap::from([("a".to_string(), 9)]),
+        small_value: 0,
+        label: "x".to_string(),
+    };
+    let json = serde_json::to_value(&numbers).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "big": "-9223372036854775808",
+            "maybe": "0",
+            "many": ["1", "-2"],
+            "rawData": "+/8=",
+            "counts": {"a": "9"},
+            "customName": "x",
+        })
+    );
+    let back: Numbers = serde_json::from_value(json).unwrap();
+    assert_eq!(back, numbers);
+}
+
+#[test]
+fn test_json_mapping_lenient_input() {
+    let json = r#"{"big": 5, "maybe": null, "many": [1, "2"], "raw_data": "-_8", "counts": null, "small_value": 3, "label": "y"}"#;
+    let numbers: Numbers = serde_json::from_str(json).unwrap();
+    assert_eq!(numbers.big, 5);
+    assert_eq!(numbers.maybe, None);
+    assert_eq!(numbers.many, vec![1, 2]);
+    assert_eq!(numbers.raw_data, vec![0xfb, 0xff]);
+    assert!(numbers.counts.is_empty());
+    assert_eq!(numbers.small_value, 3);
+    assert_eq!(numbers.label, "y");
+
+    assert!(serde_json::from_str::<Numbers>(r#"{"big": "five"}"#).is_err());
+    assert!(serde_json::from_str::<Numbers>(r#"{"big": 1.5}"#).is_err());
+    assert!(serde_json::from_str::<Numbers>(r#"{"maybe": -1}"#).is_err());
+    assert!(serde_json::from_str::<Numbers>(r#"{"rawData": "!"}"#).is_err());
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum Color {
+    Unspecified = 0,
+    Red = 1,
+}
+
+impl Color {
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Color::Unspecified => "COLOR_UNSPECIFIED",
+            Color::Red => "RED",
+        }
+    }
+
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "COLOR_UNSPECIFIED" => Some(Self::Unspecified),
+            "RED" => Some(Self::Red),
+            _ => None,
+        }
+    }
+}
+
+// Helper module as generated by `prost_wkt_build::add_serde`.
+pub mod prost_wkt_enum_serde {
+    pub mod color {
+        ::prost_wkt::proto_enum_serde!(super::super::Color, "json.test.Color");
+    }
+}
+
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Palette {
+    #[serde(rename = "main", skip_serializing_if = "::prost_wkt::json::is_default", with = "prost_wkt_enum_serde::color")]
+    #[prost(enumeration = "Color", tag = "1")]
+    pub main: i32,
+    #[serde(rename = "maybe", skip_serializing_if = "::prost_wkt::json::is_default", with = "prost_wkt_enum_serde::color::optional")]
+    #[prost(enumeration = "Color", optional, tag = "2")]
+    pub maybe: ::core::option::Option<i32>,
+    #[serde(rename = "colors", skip_serializing_if = "::prost_wkt::json::is_default", with = "prost_wkt_enum_serde::color::repeated")]
+    #[prost(enumeration = "Color", repeated, tag = "3")]
+    pub colors: ::prost::alloc::vec::Vec<i32>,
+    #[serde(rename = "byName", alias = "by_name", skip_serializing_if = "::prost_wkt::json::is_default", with = "prost_wkt_enum_serde::color::map")]
+    #[prost(map = "string, enumeration(Color)", tag = "4")]
+    pub by_name: HashMap<::prost::alloc::string::String, i32>,
+}
+
+#[test]
+fn test_json_enums() {
+    let palette = Palette {
+        main: Color::Red as i32,
+        maybe: Some(0),
+        colors: vec![1, 7],
+        by_name: HashMap::from([("a".to_string(), 1)]),
+    };
+    let json = serde_json::to_value(&palette).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "main": "RED",
+            "maybe": "COLOR_UNSPECIFIED",
+            "colors": ["RED", 7],
+            "byName": {"a": "RED"},
+        })
+    );
+    let back: Palette = serde_json::from_value(json).unwrap();
+    assert_eq!(back, palette);
+
+    let numbers: Palette = serde_json::from_str(r#"{"main": 1, "colors": [0, "RED"], "maybe": null}"#).unwrap();
+    assert_eq!(numbers.main, 1);
+    assert_eq!(numbers.colors, vec![0, 1]);
+    assert_eq!(numbers.maybe, None);
+
+    let err = serde_json::from_str::<Palette>(r#"{"main": "BLUE"}"#).unwrap_err();
+    assert!(err.to_string().starts_with("unknown value \"BLUE\" of enum json.test.Color"));
+}